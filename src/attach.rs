@@ -0,0 +1,166 @@
+use asr::{arrayvec::ArrayVec, future::next_tick, settings::Gui, Address, Process, ProcessId};
+
+use crate::{log, sigscan::find_exp_pattern};
+
+/// Names SC2 runs under, in the order they are tried. Under Wine or Proton the process has the name of the game,
+/// unless the runtime only sees Wine's preloader, which the exp scan then has to tell apart from other Wine programs.
+pub const PROCESS_NAMES: [&str; 4] = [
+    "SC2_x64.exe",
+    "SC2.exe",
+    "wine64-preloader",
+    "wine-preloader",
+];
+/// Largest number of processes that are considered at once
+const MAX_CANDIDATES: usize = 8;
+/// Ticks to wait for the exp of one of several processes to change before settling on the preferred one
+const LIVE_CHANGE_TICKS: u32 = 300;
+/// Largest number of processes without the exp that are remembered, so that they are not scanned again right away
+const MAX_REJECTED: usize = 32;
+/// Times the processes are looked for before one that was found without the exp is scanned again, in case it is SC2
+/// before it has the exp in it
+const RESCAN_ROUNDS: u32 = 600;
+
+/// Which process to prefer when several SC2 processes are running, by process id
+#[derive(Gui, Debug, Clone, Copy, PartialEq)]
+pub enum ProcessPreference {
+    /// Newest
+    #[default]
+    Newest,
+    /// Oldest
+    Oldest,
+}
+
+/// A process with the exp in it, and the exp as it was last read
+struct Candidate {
+    process: Process,
+    exp_pointer: Address,
+    exp: Option<i32>,
+}
+
+/// Processes that were scanned without finding the exp, along with the round they were scanned in. Every Wine program
+/// runs under the name of Wine's preloader, so without these they would all be scanned again each time.
+#[derive(Default)]
+struct Rejected {
+    pids: ArrayVec<(ProcessId, u32), MAX_REJECTED>,
+}
+
+impl Rejected {
+    /// Remembers a process that was scanned in the round, forgetting the one scanned longest ago if there are too many
+    fn insert(&mut self, pid: ProcessId, round: u32) {
+        if self.pids.is_full() {
+            self.pids.remove(0);
+        }
+        self.pids.push((pid, round));
+    }
+    /// Whether the process was scanned recently enough not to be scanned again in the round
+    fn contains(&self, pid: ProcessId, round: u32) -> bool {
+        self.pids.iter().any(|&(rejected, rejected_round)| {
+            rejected == pid && round.wrapping_sub(rejected_round) < RESCAN_ROUNDS
+        })
+    }
+}
+
+/// Returns the ids of every process with one of the names that is not rejected in the round, most preferred first
+fn candidate_pids(
+    names: &[&str],
+    preference: ProcessPreference,
+    rejected: &Rejected,
+    round: u32,
+) -> ArrayVec<ProcessId, MAX_CANDIDATES> {
+    let mut pids = ArrayVec::<ProcessId, MAX_CANDIDATES>::new();
+    for name in names {
+        for pid in Process::list_by_name::<MAX_CANDIDATES>(name)
+            .into_iter()
+            .flatten()
+        {
+            if rejected.contains(pid, round) {
+                continue;
+            }
+            if !pids.contains(&pid) && pids.try_push(pid).is_err() {
+                log!("Too many processes to choose from, only the first {MAX_CANDIDATES} are considered!");
+            }
+        }
+    }
+    match preference {
+        ProcessPreference::Newest => pids.sort_unstable_by(|a, b| b.cmp(a)),
+        ProcessPreference::Oldest => pids.sort_unstable(),
+    }
+    pids
+}
+
+/// Waits until an SC2 process with the exp in it can be attached to, returning it along with the address of the exp.
+/// When several have the exp, such as a replay viewer alongside the game, the first whose exp changes is chosen,
+/// or the preferred one if none of them change for a while.
+pub async fn wait_attach(preference: ProcessPreference) -> (Process, Address) {
+    wait_attach_by_names(&PROCESS_NAMES, preference).await
+}
+
+/// Like `wait_attach`, for processes with any of the names
+pub async fn wait_attach_by_names(
+    names: &[&str],
+    preference: ProcessPreference,
+) -> (Process, Address) {
+    let mut rejected = Rejected::default();
+    let mut round: u32 = 0;
+    loop {
+        let mut candidates = ArrayVec::<Candidate, MAX_CANDIDATES>::new();
+        for pid in candidate_pids(names, preference, &rejected, round) {
+            let Some(process) = Process::attach_by_pid(pid) else {
+                continue;
+            };
+            match find_exp_pattern(&process).await {
+                Some(exp_pointer) => candidates.push(Candidate {
+                    process,
+                    exp_pointer,
+                    exp: None,
+                }),
+                None => rejected.insert(pid, round),
+            }
+        }
+        if candidates.len() > 1 {
+            log!(
+                "Found the exp in {} processes, waiting for one of them to change!",
+                candidates.len()
+            );
+            for _ in 0..LIVE_CHANGE_TICKS {
+                let mut changed = None;
+                let mut index = 0;
+                while index < candidates.len() {
+                    let candidate = &mut candidates[index];
+                    // Processes whose exp can no longer be read, such as ones that closed, are dropped, which keeps
+                    // the rest in order of preference
+                    let Ok(exp) = candidate.process.read::<i32>(candidate.exp_pointer) else {
+                        candidates.remove(index);
+                        continue;
+                    };
+                    if candidate.exp.is_some_and(|old| old != exp) {
+                        changed = Some(index);
+                        break;
+                    }
+                    candidate.exp = Some(exp);
+                    index += 1;
+                }
+                if let Some(index) = changed {
+                    let candidate = candidates.remove(index);
+                    log!("Attached to the process whose exp changed!");
+                    return (candidate.process, candidate.exp_pointer);
+                }
+                if candidates.len() <= 1 {
+                    break;
+                }
+                next_tick().await;
+            }
+            if candidates.len() > 1 {
+                log!("None of the processes changed their exp, so attaching to the preferred one!");
+            }
+        }
+        // The candidates are in order of preference
+        if !candidates.is_empty() {
+            let candidate = candidates.remove(0);
+            log!("Attached to a process with the exp!");
+            return (candidate.process, candidate.exp_pointer);
+        }
+        round = round.wrapping_add(1);
+        next_tick().await;
+    }
+}
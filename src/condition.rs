@@ -0,0 +1,635 @@
+//! Custom split conditions, written as small expressions such as `level == Odin && cubes >= 3`
+//! or `pad == 10 on Level7`, that are parsed once at startup and evaluated against every state change.
+//!
+//! Grammar (loosest binding first):
+//! - `a || b`
+//! - `a && b`
+//! - `!a`
+//! - `a on LEVEL`, shorthand for `a && old_level == LEVEL`
+//! - `(a)` or `FIELD OP VALUE` where OP is one of `== != < <= > >=`
+//!
+//! Values are integers, level names (`Level1`, `Bot2000`, ..., `CowLevel`), difficulty names (`Normal`, `Hard`, `Insane`)
+//! or `true`/`false`. Levels compare by their raw level and difficulties by their multiplier.
+//! Parentheses and `!` nest at most 16 deep.
+//!
+//! Conditions are evaluated on every state change, also while tracking is invalid or the difficulty is unknown,
+//! so they can check `valid` and `difficulty` themselves. Counted fields such as `pads` only count while both are
+//! known.
+
+use core::fmt;
+
+use asr::arrayvec::ArrayVec;
+
+use crate::split_type::{Difficulty, SplitType};
+
+/// Largest number of nodes a single condition can parse into
+const MAX_NODES: usize = 32;
+/// Deepest that parentheses and `!` can nest in a condition, which bounds how far the parser recurses
+const MAX_DEPTH: usize = 16;
+
+const LEVELS: [(&str, SplitType); 13] = [
+    ("Level1", SplitType::Level1),
+    ("Level2", SplitType::Level2),
+    ("Level3", SplitType::Level3),
+    ("Bot2000", SplitType::Bot2000),
+    ("Level4", SplitType::Level4),
+    ("Level5", SplitType::Level5),
+    ("Level6", SplitType::Level6),
+    ("Odin", SplitType::Odin),
+    ("Level7", SplitType::Level7),
+    ("Level8", SplitType::Level8),
+    ("Level9", SplitType::Level9),
+    ("Diablo", SplitType::Diablo),
+    ("CowLevel", SplitType::CowLevel),
+];
+
+const DIFFICULTIES: [(&str, Difficulty); 3] = [
+    ("Normal", Difficulty::Normal),
+    ("Hard", Difficulty::Hard),
+    ("Insane", Difficulty::Insane),
+];
+
+/// Values of a state change that a condition can refer to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    /// Raw level we are on now
+    Level,
+    /// Raw level we were on before this change
+    OldLevel,
+    Pad,
+    OldPad,
+    Exp,
+    OldExp,
+    /// Exp gained by this change
+    ExpGained,
+    /// Difficulty multiplier, if known
+    Difficulty,
+    /// 1 if valid, 0 otherwise
+    Valid,
+    /// Loop through the level cycle, starting at 1
+    Loop,
+    /// Pads crossed since the previous split
+    Pads,
+    /// Energy cubes placed since the previous split
+    Cubes,
+    /// Bosses completed since the previous split
+    Bosses,
+    /// Levels completed since the previous split
+    LevelsCompleted,
+    /// Exp gained since the previous split
+    ExpSinceSplit,
+    /// Exp the bank loaded at the start of the game
+    BaseExp,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "level" => Field::Level,
+            "old_level" => Field::OldLevel,
+            "pad" => Field::Pad,
+            "old_pad" => Field::OldPad,
+            "exp" => Field::Exp,
+            "old_exp" => Field::OldExp,
+            "exp_gained" => Field::ExpGained,
+            "difficulty" => Field::Difficulty,
+            "valid" => Field::Valid,
+            "loop" => Field::Loop,
+            "pads" => Field::Pads,
+            "cubes" => Field::Cubes,
+            "bosses" => Field::Bosses,
+            "levels_completed" => Field::LevelsCompleted,
+            "exp_since_split" => Field::ExpSinceSplit,
+            "base_exp" => Field::BaseExp,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn apply(&self, lhs: i32, rhs: i32) -> bool {
+        match self {
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+            Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Compare { field: Field, op: Op, value: i32 },
+    And(u8, u8),
+    Or(u8, u8),
+    Not(u8),
+    On { node: u8, raw_level: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedCharacter,
+    UnexpectedEnd,
+    UnexpectedToken,
+    UnknownField,
+    UnknownValue,
+    UnknownLevel,
+    TooComplex,
+    TooDeep,
+}
+
+/// Error from parsing a condition, with the byte offset into the source where it happened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::UnexpectedCharacter => "unexpected character",
+            ParseErrorKind::UnexpectedEnd => "unexpected end of condition",
+            ParseErrorKind::UnexpectedToken => "unexpected token",
+            ParseErrorKind::UnknownField => "unknown field",
+            ParseErrorKind::UnknownValue => "unknown value",
+            ParseErrorKind::UnknownLevel => "unknown level",
+            ParseErrorKind::TooComplex => "condition is too complex",
+            ParseErrorKind::TooDeep => "condition is nested too deeply",
+        };
+        write!(f, "{message} at position {}", self.position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Number(i32),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    End,
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+    /// Returns the next token and its length, without consuming it
+    fn peek(&mut self) -> Result<(Token<'a>, usize), ParseError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let rest = &self.source.as_bytes()[start..];
+        let error = |kind| ParseError {
+            kind,
+            position: start,
+        };
+        let (token, len) = match rest {
+            [] => (Token::End, 0),
+            [b'=', b'=', ..] => (Token::Op(Op::Eq), 2),
+            [b'!', b'=', ..] => (Token::Op(Op::Ne), 2),
+            [b'<', b'=', ..] => (Token::Op(Op::Le), 2),
+            [b'>', b'=', ..] => (Token::Op(Op::Ge), 2),
+            [b'<', ..] => (Token::Op(Op::Lt), 1),
+            [b'>', ..] => (Token::Op(Op::Gt), 1),
+            [b'&', b'&', ..] => (Token::And, 2),
+            [b'|', b'|', ..] => (Token::Or, 2),
+            [b'!', ..] => (Token::Not, 1),
+            [b'(', ..] => (Token::Open, 1),
+            [b')', ..] => (Token::Close, 1),
+            [c, ..] if c.is_ascii_digit() || *c == b'-' => {
+                let len = 1 + rest[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let number = self.source[start..start + len]
+                    .parse()
+                    .map_err(|_| error(ParseErrorKind::UnknownValue))?;
+                (Token::Number(number), len)
+            }
+            [c, ..] if c.is_ascii_alphabetic() || *c == b'_' => {
+                let len = rest
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+                    .count();
+                (Token::Ident(&self.source[start..start + len]), len)
+            }
+            _ => return Err(error(ParseErrorKind::UnexpectedCharacter)),
+        };
+        Ok((token, len))
+    }
+    /// Consumes the next token, returning it and the position it starts at
+    fn next(&mut self) -> Result<(Token<'a>, usize), ParseError> {
+        let (token, len) = self.peek()?;
+        // Peeking skipped the whitespace, so we are at the start of the token
+        let start = self.position;
+        self.position += len;
+        Ok((token, start))
+    }
+}
+
+/// A parsed custom split condition
+#[derive(Debug, Clone)]
+pub struct Condition {
+    nodes: ArrayVec<Node, MAX_NODES>,
+    root: u8,
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    nodes: ArrayVec<Node, MAX_NODES>,
+    /// How many parentheses and `!` the parser is inside of
+    depth: usize,
+}
+
+impl Parser<'_> {
+    /// Parses with `parse`, one level deeper
+    fn nested(
+        &mut self,
+        position: usize,
+        parse: impl FnOnce(&mut Self) -> Result<u8, ParseError>,
+    ) -> Result<u8, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                position,
+            });
+        }
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+        node
+    }
+    fn push(&mut self, node: Node) -> Result<u8, ParseError> {
+        let index = self.nodes.len() as u8;
+        self.nodes.try_push(node).map_err(|_| ParseError {
+            kind: ParseErrorKind::TooComplex,
+            position: self.lexer.position,
+        })?;
+        Ok(index)
+    }
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        match self.lexer.next()? {
+            (Token::End, _) => Ok(()),
+            (_, position) => Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                position,
+            }),
+        }
+    }
+    fn parse_or(&mut self) -> Result<u8, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while let (Token::Or, _) = self.lexer.peek()? {
+            self.lexer.next()?;
+            let rhs = self.parse_and()?;
+            lhs = self.push(Node::Or(lhs, rhs))?;
+        }
+        Ok(lhs)
+    }
+    fn parse_and(&mut self) -> Result<u8, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        while let (Token::And, _) = self.lexer.peek()? {
+            self.lexer.next()?;
+            let rhs = self.parse_unary()?;
+            lhs = self.push(Node::And(lhs, rhs))?;
+        }
+        Ok(lhs)
+    }
+    fn parse_unary(&mut self) -> Result<u8, ParseError> {
+        if let (Token::Not, _) = self.lexer.peek()? {
+            let (_, position) = self.lexer.next()?;
+            let node = self.nested(position, Self::parse_unary)?;
+            return self.push(Node::Not(node));
+        }
+        let node = self.parse_primary()?;
+        if let (Token::Ident("on"), _) = self.lexer.peek()? {
+            self.lexer.next()?;
+            let (token, position) = self.lexer.next()?;
+            let raw_level = match token {
+                Token::Ident(name) => level_from_name(name).and_then(|level| level.raw_level()),
+                _ => None,
+            }
+            .ok_or(ParseError {
+                kind: ParseErrorKind::UnknownLevel,
+                position,
+            })?;
+            return self.push(Node::On { node, raw_level });
+        }
+        Ok(node)
+    }
+    fn parse_primary(&mut self) -> Result<u8, ParseError> {
+        match self.lexer.next()? {
+            (Token::Open, position) => {
+                let node = self.nested(position, Self::parse_or)?;
+                match self.lexer.next()? {
+                    (Token::Close, _) => Ok(node),
+                    (_, position) => Err(ParseError {
+                        kind: ParseErrorKind::UnexpectedToken,
+                        position,
+                    }),
+                }
+            }
+            (Token::Ident(name), position) => {
+                let field = Field::from_name(name).ok_or(ParseError {
+                    kind: ParseErrorKind::UnknownField,
+                    position,
+                })?;
+                let op = match self.lexer.next()? {
+                    (Token::Op(op), _) => op,
+                    (_, position) => {
+                        return Err(ParseError {
+                            kind: ParseErrorKind::UnexpectedToken,
+                            position,
+                        })
+                    }
+                };
+                let value = self.parse_value()?;
+                self.push(Node::Compare { field, op, value })
+            }
+            (Token::End, position) => Err(ParseError {
+                kind: ParseErrorKind::UnexpectedEnd,
+                position,
+            }),
+            (_, position) => Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                position,
+            }),
+        }
+    }
+    fn parse_value(&mut self) -> Result<i32, ParseError> {
+        let (token, position) = self.lexer.next()?;
+        let value = match token {
+            Token::Number(number) => Some(number),
+            Token::Ident("true") => Some(1),
+            Token::Ident("false") => Some(0),
+            Token::Ident(name) => level_from_name(name)
+                .and_then(|level| level.raw_level())
+                .or_else(|| {
+                    DIFFICULTIES
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, difficulty)| *difficulty as i32)
+                }),
+            Token::End => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::UnexpectedEnd,
+                    position,
+                })
+            }
+            _ => None,
+        };
+        value.ok_or(ParseError {
+            kind: ParseErrorKind::UnknownValue,
+            position,
+        })
+    }
+}
+
+fn level_from_name(name: &str) -> Option<SplitType> {
+    LEVELS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, level)| *level)
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            lexer: Lexer {
+                source,
+                position: 0,
+            },
+            nodes: ArrayVec::new(),
+            depth: 0,
+        };
+        let root = parser.parse_or()?;
+        parser.expect_end()?;
+        Ok(Self {
+            nodes: parser.nodes,
+            root,
+        })
+    }
+    /// Evaluates the condition, looking up field values with the given function.
+    /// Comparisons against a field without a value (such as an unknown difficulty) are false.
+    pub fn evaluate(&self, value: impl Fn(Field) -> Option<i32>) -> bool {
+        self.evaluate_node(self.root, &value)
+    }
+    fn evaluate_node(&self, index: u8, value: &impl Fn(Field) -> Option<i32>) -> bool {
+        match self.nodes[index as usize] {
+            Node::Compare {
+                field,
+                op,
+                value: rhs,
+            } => value(field).is_some_and(|lhs| op.apply(lhs, rhs)),
+            Node::And(lhs, rhs) => self.evaluate_node(lhs, value) && self.evaluate_node(rhs, value),
+            Node::Or(lhs, rhs) => self.evaluate_node(lhs, value) || self.evaluate_node(rhs, value),
+            Node::Not(node) => !self.evaluate_node(node, value),
+            Node::On { node, raw_level } => {
+                value(Field::OldLevel) == Some(raw_level) && self.evaluate_node(node, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use asr::arrayvec::ArrayString;
+
+    use super::*;
+
+    /// `compare` nested in `depth` pairs of parentheses, or behind `depth` `!`
+    fn nested(depth: usize, parentheses: bool) -> ArrayString<256> {
+        let mut source = ArrayString::new();
+        for _ in 0..depth {
+            source.push_str(if parentheses { "(" } else { "!" });
+        }
+        source.push_str("exp >= 1000");
+        for _ in 0..depth {
+            if parentheses {
+                source.push(')');
+            }
+        }
+        source
+    }
+
+    #[test]
+    fn nests_up_to_the_depth() {
+        for parentheses in [true, false] {
+            let source = nested(MAX_DEPTH, parentheses);
+            assert!(Condition::parse(&source).is_ok(), "{source}");
+            let source = nested(MAX_DEPTH + 1, parentheses);
+            assert_eq!(
+                Condition::parse(&source).map(|_| ()),
+                Err(ParseError {
+                    kind: ParseErrorKind::TooDeep,
+                    position: MAX_DEPTH,
+                }),
+                "{source}"
+            );
+        }
+        // Far past the depth is an error rather than running out of stack
+        let mut source = ArrayString::<4096>::new();
+        for _ in 0..4000 {
+            source.push('(');
+        }
+        assert_eq!(
+            Condition::parse(&source)
+                .map_err(|error| error.kind)
+                .map(|_| ()),
+            Err(ParseErrorKind::TooDeep)
+        );
+    }
+
+    #[test]
+    fn evaluates_without_a_difficulty_or_while_invalid() {
+        let condition =
+            Condition::parse("exp >= 1000 && !(difficulty == Hard) && valid == false").unwrap();
+        let value = |field| match field {
+            Field::Exp => Some(1000),
+            Field::Valid => Some(0),
+            _ => None,
+        };
+        assert!(condition.evaluate(value));
+    }
+
+    #[test]
+    fn evaluates_the_examples() {
+        let condition = Condition::parse("level == Odin && cubes >= 3").unwrap();
+        let on_odin = |cubes| {
+            move |field| match field {
+                Field::Level => SplitType::Odin.raw_level(),
+                Field::Cubes => Some(cubes),
+                _ => None,
+            }
+        };
+        assert!(condition.evaluate(on_odin(3)));
+        assert!(!condition.evaluate(on_odin(2)));
+        assert!(!condition.evaluate(|field| match field {
+            Field::Level => SplitType::Level7.raw_level(),
+            Field::Cubes => Some(3),
+            _ => None,
+        }));
+
+        let condition = Condition::parse("pad == 10 on Level7").unwrap();
+        let crossed = |pad, old_level: SplitType| {
+            move |field| match field {
+                Field::Pad => Some(pad),
+                Field::OldLevel => old_level.raw_level(),
+                _ => None,
+            }
+        };
+        assert!(condition.evaluate(crossed(10, SplitType::Level7)));
+        assert!(!condition.evaluate(crossed(9, SplitType::Level7)));
+        assert!(!condition.evaluate(crossed(10, SplitType::Level8)));
+    }
+
+    #[test]
+    fn binds_and_tighter_than_or() {
+        let values = |exp, pad| {
+            move |field| match field {
+                Field::Exp => Some(exp),
+                Field::Pad => Some(pad),
+                Field::OldLevel => SplitType::Level1.raw_level(),
+                _ => None,
+            }
+        };
+        // exp == 1 || (exp == 2 && pad == 3)
+        let condition = Condition::parse("exp == 1 || exp == 2 && pad == 3").unwrap();
+        assert!(condition.evaluate(values(1, 0)));
+        assert!(condition.evaluate(values(2, 3)));
+        assert!(!condition.evaluate(values(2, 0)));
+        // (exp == 2 && pad == 3) || exp == 1
+        let condition = Condition::parse("exp == 2 && pad == 3 || exp == 1").unwrap();
+        assert!(condition.evaluate(values(1, 0)));
+        // (!(exp == 1)) || pad == 3
+        let condition = Condition::parse("!exp == 1 || pad == 3").unwrap();
+        assert!(condition.evaluate(values(1, 3)));
+        assert!(!condition.evaluate(values(1, 0)));
+        // exp == 1 || (pad == 3 on Level7), and the old level is Level1
+        let condition = Condition::parse("exp == 1 || pad == 3 on Level7").unwrap();
+        assert!(condition.evaluate(values(1, 0)));
+        assert!(!condition.evaluate(values(0, 3)));
+        // Parentheses group before the rest
+        let condition = Condition::parse("(exp == 1 || exp == 2) && pad == 3").unwrap();
+        assert!(!condition.evaluate(values(1, 0)));
+        assert!(condition.evaluate(values(1, 3)));
+    }
+
+    #[test]
+    fn reads_level_and_difficulty_names() {
+        for (name, level) in LEVELS {
+            let mut source = ArrayString::<32>::new();
+            write!(source, "level == {name}").unwrap();
+            let condition = Condition::parse(&source).unwrap();
+            assert!(condition
+                .evaluate(|field| (field == Field::Level).then(|| level.raw_level()).flatten()));
+        }
+        for (name, difficulty) in DIFFICULTIES {
+            let mut source = ArrayString::<32>::new();
+            write!(source, "difficulty == {name}").unwrap();
+            let condition = Condition::parse(&source).unwrap();
+            assert!(condition
+                .evaluate(|field| (field == Field::Difficulty).then_some(difficulty as i32)));
+        }
+        // Difficulties compare by their multiplier
+        let condition = Condition::parse("difficulty > Normal").unwrap();
+        assert!(condition.evaluate(|_| Some(Difficulty::Hard as i32)));
+        assert!(!condition.evaluate(|_| Some(Difficulty::Normal as i32)));
+        // A difficulty is a value, but not a level to be on
+        assert_eq!(
+            Condition::parse("pad == 1 on Normal").map(|_| ()),
+            Err(ParseError {
+                kind: ParseErrorKind::UnknownLevel,
+                position: 12,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_what_failed_and_where() {
+        let cases = [
+            ("levl == 1", ParseErrorKind::UnknownField, 0),
+            ("level == Level10", ParseErrorKind::UnknownValue, 9),
+            ("exp >= 99999999999", ParseErrorKind::UnknownValue, 7),
+            ("pad == 1 on Odinn", ParseErrorKind::UnknownLevel, 12),
+            ("exp = 1", ParseErrorKind::UnexpectedCharacter, 4),
+            ("exp 1000", ParseErrorKind::UnexpectedToken, 4),
+            ("exp >= 1000)", ParseErrorKind::UnexpectedToken, 11),
+            ("(exp >= 1000 pad", ParseErrorKind::UnexpectedToken, 13),
+            ("exp >= 1000 &&", ParseErrorKind::UnexpectedEnd, 14),
+            ("exp >=", ParseErrorKind::UnexpectedEnd, 6),
+            ("", ParseErrorKind::UnexpectedEnd, 0),
+        ];
+        for (source, kind, position) in cases {
+            assert_eq!(
+                Condition::parse(source).map(|_| ()),
+                Err(ParseError { kind, position }),
+                "{source}"
+            );
+        }
+        let error = Condition::parse("exp >= 1000 || levl == 1").unwrap_err();
+        let mut message = ArrayString::<64>::new();
+        write!(message, "{error}").unwrap();
+        assert_eq!(message.as_str(), "unknown field at position 15");
+    }
+}
@@ -1,22 +1,47 @@
 use asr::{future::next_tick, watcher::Pair, Address, Process};
 
 use crate::{
+    condition::Field,
+    deaths::{DeathEvent, DeathTracker},
+    game_clock::{ClockEvent, GameClock, GameSpeed},
+    lifecycle::GameState,
     log,
-    sigscan::find_exp_pattern,
-    split_state::SplitState,
+    progress::Progress,
+    route::Route,
+    sigscan::{find_exp_pattern, has_exp_signature},
+    split_state::{EventKind, SplitState},
     split_type::{any_boss, Difficulty, SplitType, LARGEST_EXP_DIFFERENCE},
 };
 
-const PAD_COUNT: i32 = 19;
-
 pub struct GameData<'a> {
     process: &'a Process,
     exp_pointer: Option<Address>,
     current_exp: Option<i32>,
     level: SplitType,
+    /// Level that tracking starts from, which is the first level unless the game is known to start on another
+    start_level: SplitType,
     current_pad: i32,
+    /// Which pass through the level cycle we are on, starting at 1 and incremented after the cow level
+    current_loop: i32,
     valid: bool,
+    /// Whether the exp struct went away, which happens when the game is left rather than when the exp is garbage
+    game_left: bool,
     difficulty: Option<Difficulty>,
+    /// The game loop, which is looked for around the exp only when asked for, since telling it apart from other
+    /// counters is a heuristic that has not been checked against SC2
+    clock: Option<GameClock<'a>>,
+    /// Deaths are only followed when asked for, since where the alive flag is kept is not verified
+    track_deaths: bool,
+    death_tracker: DeathTracker<'a>,
+    /// Deaths over the run and on the level we are on
+    deaths: i32,
+    level_deaths: i32,
+    /// Exp the bank loaded at the start of the game, which the run counts from
+    base_exp: Option<i32>,
+    /// The last exp above 0 that was seen before this game, which the bank was most likely saved with
+    last_seen_exp: Option<i32>,
+    /// Whether the last exp read was the bank being loaded
+    bank_loaded: bool,
 }
 
 #[derive(Copy, Clone)]
@@ -24,8 +49,17 @@ pub struct StateChange {
     pads: Pair<i32>,
     exps: Pair<Option<i32>>,
     levels: Pair<SplitType>,
+    loops: Pair<i32>,
     valid: Pair<bool>,
     difficulty: Pair<Option<Difficulty>>,
+    clock_event: Option<ClockEvent>,
+    /// The game loop as read along with the exp, once the game loop has been found
+    game_loop: Option<u32>,
+    deaths: Pair<i32>,
+    level_deaths: Pair<i32>,
+    death_event: Option<DeathEvent>,
+    bank_loaded: bool,
+    base_exp: Option<i32>,
 }
 
 async fn find_and_ret_pattern(process: &Process) -> Address {
@@ -40,14 +74,29 @@ async fn find_and_ret_pattern(process: &Process) -> Address {
 impl<'a> GameData<'a> {
     pub async fn new(process: &'a Process) -> GameData<'a> {
         // Try to find the address in the process
+        Self::with_exp_pointer(process, find_and_ret_pattern(process).await)
+    }
+    /// Starts tracking with the exp at an address that was already found
+    pub fn with_exp_pointer(process: &'a Process, exp_pointer: Address) -> GameData<'a> {
         Self {
             process,
-            exp_pointer: Some(find_and_ret_pattern(process).await),
+            exp_pointer: Some(exp_pointer),
             current_exp: None,
             level: SplitType::Level1,
+            start_level: SplitType::Level1,
             current_pad: 0,
+            current_loop: 1,
             valid: true,
+            game_left: false,
             difficulty: None,
+            clock: None,
+            track_deaths: false,
+            death_tracker: DeathTracker::disabled(process),
+            deaths: 0,
+            level_deaths: 0,
+            base_exp: None,
+            last_seen_exp: None,
+            bank_loaded: false,
         }
     }
 }
@@ -58,6 +107,12 @@ impl GameData<'_> {
         match find_exp_pattern(self.process).await {
             Some(val) => {
                 self.exp_pointer = Some(val);
+                if self.clock.is_some() {
+                    self.clock = Some(GameClock::new(self.process, val));
+                }
+                if self.track_deaths {
+                    self.death_tracker = DeathTracker::new(self.process, val);
+                }
                 true
             }
             None => {
@@ -66,12 +121,90 @@ impl GameData<'_> {
             }
         }
     }
+    /// Looks for the game loop around the exp and follows it, which is experimental since the game loop is told apart
+    /// from other counters by how fast it advances, which has not been checked against SC2
+    pub fn follow_game_loop(&mut self) {
+        if let Some(exp_pointer) = self.exp_pointer {
+            self.clock = Some(GameClock::new(self.process, exp_pointer));
+        }
+    }
+    /// Follows deaths from the alive flag, which is experimental since where it is kept is not verified
+    pub fn track_deaths(&mut self) {
+        self.track_deaths = true;
+        if let Some(exp_pointer) = self.exp_pointer {
+            self.death_tracker = DeathTracker::new(self.process, exp_pointer);
+        }
+    }
+    /// Returns true if deaths are followed
+    pub fn tracks_deaths(&self) -> bool {
+        self.track_deaths
+    }
+    /// Tracks from the start of the level instead of the first level, for a game that starts on it, such as when
+    /// practicing it. Which level a game starts on cannot be read, so it has to be told.
+    pub fn start_at(&mut self, level: SplitType) {
+        self.start_level = level;
+        self.level = level;
+        self.current_pad = 0;
+    }
+    /// Starts tracking over from the level it started from, keeping the exp pointer, for when the run is restarted in
+    /// LiveSplit. The next exp read becomes the new starting point, like when the pattern is first found.
+    pub fn reset_tracking(&mut self) {
+        self.current_exp = None;
+        self.level = self.start_level;
+        self.current_pad = 0;
+        self.current_loop = 1;
+        self.difficulty = None;
+        self.deaths = 0;
+        self.level_deaths = 0;
+        self.base_exp = None;
+    }
+    /// Returns where the game is at for a snapshot of the run, or None before the first exp read
+    pub fn progress(&self, split_index: usize, route_len: usize) -> Option<Progress> {
+        Some(Progress {
+            exp: self.current_exp?,
+            level: self.level,
+            pad: self.current_pad,
+            loop_num: self.current_loop,
+            difficulty: self.difficulty,
+            deaths: self.deaths,
+            split_index,
+            route_len,
+            game_time: None,
+        })
+    }
+    /// Carries on tracking from a snapshot of the run, if the exp in game is still what it was when it was taken.
+    /// Returns true if the snapshot was resumed.
+    pub fn resume(&mut self, progress: &Progress) -> bool {
+        let Some(exp) = self.read_exp() else {
+            return false;
+        };
+        if exp != progress.exp {
+            log!(
+                "Not resuming because the exp: {exp} does not match the saved exp: {}!",
+                progress.exp
+            );
+            return false;
+        }
+        self.current_exp = Some(exp);
+        self.level = progress.level;
+        self.current_pad = progress.pad;
+        self.current_loop = progress.loop_num;
+        self.difficulty = progress.difficulty;
+        self.deaths = progress.deaths;
+        self.level_deaths = 0;
+        true
+    }
     fn read_exp(&mut self) -> Option<i32> {
         if let Some(ptr) = self.exp_pointer {
             match self.process.read::<i32>(ptr) {
                 // Exp is stored as a multiple of 4096, so compute that here
                 Ok(val) => {
-                    if val % 4096 != 0 {
+                    if !has_exp_signature(self.process, ptr) {
+                        log!("Invalidating because the exp struct is gone, so the game was left!");
+                        self.game_left = true;
+                        self.invalidate();
+                        None
+                    } else if val % 4096 != 0 {
                         log!("Invalidating because we read back exp: {val} that was not a multiple of 4096!");
                         self.invalidate();
                         None
@@ -80,7 +213,9 @@ impl GameData<'_> {
                     }
                 }
                 _ => {
-                    log!("Process read failed for exp read!");
+                    // The memory of a game is freed when it is left
+                    log!("Process read failed for exp read, so the game was left!");
+                    self.game_left = true;
                     self.invalidate();
                     None
                 }
@@ -99,18 +234,81 @@ impl GameData<'_> {
     pub fn invalid(&self) -> bool {
         !self.valid
     }
+    /// Returns the state of the game as seen by the last update, or None if tracking was invalidated within the game.
+    /// Whether the game is won depends on the route, so the caller tells whether its last split was done.
+    pub fn game_state(&self, route_finished: bool) -> Option<GameState> {
+        Some(if self.game_left {
+            GameState::Left
+        } else if self.invalid() {
+            return None;
+        } else if route_finished {
+            GameState::Victory
+        } else if self.current_exp == Some(0) {
+            GameState::Loading
+        } else {
+            GameState::InGame
+        })
+    }
+    /// Returns the last game loop that was read, once the game loop has been found
+    pub fn game_loop(&self) -> Option<u32> {
+        self.clock.as_ref().and_then(GameClock::game_loop)
+    }
+    /// Returns the game speed the lobby was set to, once the game loop has been timed for long enough to tell
+    pub fn game_speed(&self) -> Option<GameSpeed> {
+        self.clock.as_ref().and_then(GameClock::game_speed)
+    }
     pub fn exp(&self) -> Option<i32> {
         self.current_exp
     }
+    pub fn level(&self) -> SplitType {
+        self.level
+    }
+    /// Returns the exp the bank loaded at the start of the game, which the run counts from
+    pub fn base_exp(&self) -> Option<i32> {
+        self.base_exp
+    }
+    /// Sets the last exp above 0 that was seen, such as in the last game, so that loading a bank with it is known to
+    /// be a bank load even when it is small enough to have been gained. This is a guess at what the bank was saved
+    /// with, which it is whenever the exp changes; the bank itself is not read.
+    pub fn remember_exp(&mut self, exp: i32) {
+        self.last_seen_exp = Some(exp);
+    }
+    /// Returns true if the exp changing from the old exp is the bank being loaded rather than exp being gained.
+    /// Until the bank is loaded, the exp struct is 0, or still has an exp from before that was read as the bank, and
+    /// nothing has been gained since. Loading the bank then jumps straight to the saved exp, which is usually not a
+    /// change that can be gained at once.
+    fn is_bank_load(&self, old_exp: i32, exp: i32) -> bool {
+        let before_bank = old_exp == 0 || Some(old_exp) == self.base_exp;
+        before_bank
+            && exp > 0
+            && (!(0..=LARGEST_EXP_DIFFERENCE).contains(&(exp - old_exp))
+                || Some(exp) == self.last_seen_exp)
+    }
+    /// Returns the deaths over the run, and on the level we are on
+    pub fn deaths(&self) -> (i32, i32) {
+        (self.deaths, self.level_deaths)
+    }
     /// Returns the exp difference, if present. If garbage or invalid, None is returned and the state is reset.
     fn update_exp(&mut self) -> Option<i32> {
+        self.bank_loaded = false;
         if let Some(exp) = self.read_exp() {
-            let difference = if let Some(old_exp) = self.current_exp {
-                exp - old_exp
-            } else {
-                log!("Initial exp read as: {exp}");
-                0
+            let difference = match self.current_exp {
+                Some(old_exp) if exp != old_exp && self.is_bank_load(old_exp, exp) => {
+                    log!("Bank loaded with exp: {exp}");
+                    self.bank_loaded = true;
+                    0
+                }
+                Some(old_exp) => exp - old_exp,
+                None => {
+                    log!("Initial exp read as: {exp}");
+                    // The first read after the game starts is the bank, unless it is still loading
+                    self.bank_loaded = exp > 0;
+                    0
+                }
             };
+            if self.bank_loaded {
+                self.base_exp = Some(exp);
+            }
             self.current_exp = Some(exp);
             if !(0..=LARGEST_EXP_DIFFERENCE).contains(&difference) {
                 // Invalid difference
@@ -118,22 +316,29 @@ impl GameData<'_> {
                 self.invalidate();
                 return None;
             }
-            // If we had enough exp for a pad, we increment our pad counter by 1.
+            // If we had enough exp for pads, we increment our pad counter by them.
             match self.difficulty {
                 Some(diff) => {
-                    if self.level.is_normal_level() {
-                        // If we have obtained exactly enough exp for a pad, increment our pad counter
+                    if let (Some(pad_count), Some(per_pad_exp)) =
+                        (self.level.pad_count(), self.level.per_pad_exp(diff))
+                    {
+                        // If we have obtained exactly enough exp for some pads, increment our pad counter by them.
+                        // The game can step more than once between reads, so one read can take in several pads.
                         // TODO: Note that this only works if WE are the ones going through the level
-                        if difference
-                            == self
-                                .level
-                                .per_pad_exp(diff)
-                                .expect("Level is a normal level, so must have valid pad exp")
+                        let pads = difference / per_pad_exp;
+                        if difference > 0
+                            && difference % per_pad_exp == 0
+                            && self.current_pad + pads <= pad_count
                         {
                             let pad = self.current_pad;
-                            let new_pad = self.current_pad + 1;
+                            let new_pad = self.current_pad + pads;
                             log!("Crossed pad! Previous pad was: {pad}, pad just crossed is: {new_pad}!");
                             self.current_pad = new_pad;
+                        } else if difference > 0 {
+                            // This can be the last pads of the level read along with the next level, which cannot
+                            // be told apart from other gains, so no pads are counted for it
+                            let level = self.level;
+                            log!("Gained {difference} exp, which is not a whole number of the pads left on {level:?}, so no pads were counted!");
                         }
                     }
                 }
@@ -142,15 +347,15 @@ impl GameData<'_> {
                     // TODO: Note that this only works if WE are the ones going through the level
                     // TODO: Some compile time check to ensure we capture all difficulties as we iterate
                     if self.level.is_normal_level() {
-                        if difference == self.level.standard_exp(Difficulty::Normal) {
+                        if Some(difference) == self.level.standard_exp(Difficulty::Normal) {
                             log!("Determined difficulty to be Normal!");
                             self.difficulty = Some(Difficulty::Normal);
                             self.current_pad = 1;
-                        } else if difference == self.level.standard_exp(Difficulty::Hard) {
+                        } else if Some(difference) == self.level.standard_exp(Difficulty::Hard) {
                             log!("Determined difficulty to be Hard!");
                             self.difficulty = Some(Difficulty::Hard);
                             self.current_pad = 1;
-                        } else if difference == self.level.standard_exp(Difficulty::Insane) {
+                        } else if Some(difference) == self.level.standard_exp(Difficulty::Insane) {
                             log!("Determined difficulty to be Insane!");
                             self.difficulty = Some(Difficulty::Insane);
                             self.current_pad = 1;
@@ -163,6 +368,28 @@ impl GameData<'_> {
         }
         None
     }
+    /// Moves on to the next level, invalidating if we are somehow not on a level
+    fn next_level(&mut self) {
+        match self.level.next() {
+            Some(level) => {
+                // Completing the cow level loops us back around to the first level
+                if self.level == SplitType::CowLevel {
+                    self.current_loop += 1;
+                    let current_loop = self.current_loop;
+                    log!("Starting loop: {current_loop}!");
+                } else if level == SplitType::CowLevel {
+                    log!("Entered the cow level!");
+                }
+                self.level = level;
+                self.level_deaths = 0;
+            }
+            None => {
+                let level = self.level;
+                log!("Invalidating because we cannot move on from: {level:?}");
+                self.invalidate();
+            }
+        }
+    }
     // TODO: The way this function is written is not conductive to midgame runs or practice.
     // This is because it is assumed that the split is not relevant for the update of this logic.
     // Sometimes, however, the split/game info is useful in telling us things like the difficulty, level, pad count, etc.
@@ -171,34 +398,70 @@ impl GameData<'_> {
         // Initial point is the current state
         let old_level = self.level;
         let old_pad = self.current_pad;
+        let old_loop = self.current_loop;
         let old_exp = self.current_exp;
         let old_valid = self.valid;
         let old_diff = self.difficulty;
+        let old_deaths = self.deaths;
+        let old_level_deaths = self.level_deaths;
+        // The game loop is read right before the exp, so any change to the exp is known to have happened by then
+        let clock_event = self.clock.as_mut().and_then(GameClock::update);
+        let game_loop = self.game_loop();
+        let death_event = self.death_tracker.update();
+        if death_event == Some(DeathEvent::Died) {
+            self.deaths += 1;
+            self.level_deaths += 1;
+            let (deaths, level_deaths) = (self.deaths, self.level_deaths);
+            log!("Died! Deaths are now: {deaths}, on this level: {level_deaths}");
+        } else if death_event == Some(DeathEvent::Revived) {
+            log!("Revived!");
+        }
         // Update our exp
         self.update_exp();
+        // Loading the bank is not gaining exp, so the change starts from the exp it loaded
+        let old_exp = match self.bank_loaded {
+            true => self.current_exp,
+            false => old_exp,
+        };
         // Capture new state info
         if !self.invalid() {
             // Check to see if we need to complete a level based off of pad or exp
-            if self.current_pad == PAD_COUNT {
+            if Some(self.current_pad) == self.level.pad_count() {
                 let old_level = self.level;
-                self.level = self.level.next();
+                self.next_level();
                 let level = self.level;
                 log!("Level complete! Was: {old_level:?} now is: {level:?}");
                 self.current_pad = 0;
             } else if self.level.is_boss_level() {
                 // Check the difference here and increment the level if so
-                if let Some(diff) = self.difficulty {
-                    match (old_exp, self.current_exp) {
-                        (Some(old), Some(current)) => {
-                            if current - old == self.level.boss_exp(diff) {
-                                let old_level = self.level;
-                                self.level = self.level.next();
-                                let level = self.level;
-                                log!("Boss complete! Was: {old_level:?} now is: {level:?}");
+                match (old_exp, self.current_exp) {
+                    (Some(old), Some(current)) => {
+                        let difference = current - old;
+                        // A game that starts on a boss has no pads to tell the difficulty from, so it is told from
+                        // the boss exp instead
+                        let diff = self.difficulty.or_else(|| {
+                            let normal_exp = self.level.boss_exp(Difficulty::Normal)?;
+                            match difference > 0 && difference % normal_exp == 0 {
+                                true => Difficulty::from_multiplier(difference / normal_exp),
+                                false => None,
                             }
+                        });
+                        if let Some(diff) =
+                            diff.filter(|diff| Some(difference) == self.level.boss_exp(*diff))
+                        {
+                            if self.difficulty.is_none() {
+                                log!("Determined difficulty to be {diff:?} from the boss!");
+                                self.difficulty = Some(diff);
+                            }
+                            let old_level = self.level;
+                            self.next_level();
+                            let level = self.level;
+                            log!("Boss complete! Was: {old_level:?} now is: {level:?}");
+                            self.current_pad = 0;
                         }
-                        _ => self.invalidate(),
                     }
+                    _ if self.difficulty.is_some() => self.invalidate(),
+                    _ => (),
                 }
             }
         }
@@ -211,6 +474,10 @@ impl GameData<'_> {
                 old: old_pad,
                 current: self.current_pad,
             },
+            loops: Pair {
+                old: old_loop,
+                current: self.current_loop,
+            },
             exps: Pair {
                 old: old_exp,
                 current: self.current_exp,
@@ -223,13 +490,157 @@ impl GameData<'_> {
                 old: old_diff,
                 current: self.difficulty,
             },
+            clock_event,
+            game_loop,
+            deaths: Pair {
+                old: old_deaths,
+                current: self.deaths,
+            },
+            level_deaths: Pair {
+                old: old_level_deaths,
+                current: self.level_deaths,
+            },
+            death_event,
+            bank_loaded: self.bank_loaded,
+            base_exp: self.base_exp,
         }
     }
 }
 
 impl StateChange {
-    /// Returns true if the exp gained was exactly equivalent to a split type
-    pub fn should_split(&self, split_state: &mut SplitState, split: SplitType) -> bool {
+    /// Returns true if the given level was just started, either by completing the level before it or by crossing its first
+    /// pad. A level without pads, such as a boss, is started by the game starting on it too.
+    pub fn started_level(&self, level: SplitType) -> bool {
+        self.valid.current
+            && self.levels.current == level
+            && (self.levels.old != level
+                || (self.pads.old == 0 && self.pads.current > 0)
+                || (self.bank_loaded && level.pad_count().is_none()))
+    }
+    /// Returns whether the game was just paused or resumed, as told from the game loop
+    pub fn clock_event(&self) -> Option<ClockEvent> {
+        self.clock_event
+    }
+    /// Returns the game loop that this change happened by, once the game loop has been found
+    pub fn game_loop(&self) -> Option<u32> {
+        self.game_loop
+    }
+    /// Returns whether the runling just died or was revived
+    pub fn death_event(&self) -> Option<DeathEvent> {
+        self.death_event
+    }
+    /// Returns true if exp was just gained, which loading the bank is not
+    pub fn gained_exp(&self) -> bool {
+        matches!((self.exps.old, self.exps.current), (Some(old), Some(current)) if current > old)
+    }
+    /// Returns true if the bank was just loaded, whose exp the run counts from
+    pub fn bank_loaded(&self) -> bool {
+        self.bank_loaded
+    }
+    /// Returns true if the deaths over the run or on the level changed, which moving on to the next level does too
+    pub fn deaths_changed(&self) -> bool {
+        self.deaths.changed() || self.level_deaths.changed()
+    }
+    /// Returns the value of a field for custom conditions, if it is known
+    pub fn field_value(&self, field: Field, split_state: &SplitState) -> Option<i32> {
+        match field {
+            Field::Level => self.levels.current.raw_level(),
+            Field::OldLevel => self.levels.old.raw_level(),
+            Field::Pad => Some(self.pads.current),
+            Field::OldPad => Some(self.pads.old),
+            Field::Exp => self.exps.current,
+            Field::OldExp => self.exps.old,
+            Field::ExpGained => match (self.exps.old, self.exps.current) {
+                (Some(old), Some(current)) => Some(current - old),
+                _ => None,
+            },
+            Field::Difficulty => self.difficulty.current.map(|diff| diff as i32),
+            Field::Valid => Some(self.valid.current as i32),
+            Field::Loop => Some(self.loops.current),
+            Field::Pads => Some(split_state.count(EventKind::Pad)),
+            Field::Cubes => Some(split_state.count(EventKind::Cube)),
+            Field::Bosses => Some(split_state.count(EventKind::Boss)),
+            Field::LevelsCompleted => Some(split_state.count(EventKind::Level)),
+            Field::ExpSinceSplit => Some(split_state.count(EventKind::ExpGained)),
+            Field::BaseExp => self.base_exp,
+        }
+    }
+    /// Adds the events that happened in this change to the counts of the split state
+    fn record_events(&self, split_state: &mut SplitState, diff: Difficulty, exp_difference: i32) {
+        let raw_level_change = self.levels.old != self.levels.current;
+        if self.pads.old != self.pads.current {
+            // Completing a level by its last pads starts the pad count over
+            let pads = match raw_level_change {
+                true => self.levels.old.pad_count().unwrap_or_default() - self.pads.old,
+                false => self.pads.current - self.pads.old,
+            };
+            split_state.record(EventKind::Pad, pads);
+        }
+        // This is None for any level without cubes
+        if Some(exp_difference) == self.levels.current.cube_exp(diff) {
+            split_state.record(EventKind::Cube, 1);
+        }
+        if raw_level_change && self.levels.old.is_boss_level() {
+            split_state.record(EventKind::Boss, 1);
+        }
+        if raw_level_change {
+            split_state.record(EventKind::Level, 1);
+        }
+        if exp_difference > 0 {
+            split_state.record(EventKind::ExpGained, exp_difference);
+        }
+        if self.difficulty.old != self.difficulty.current {
+            split_state.record(EventKind::DifficultyChange, 1);
+        }
+    }
+    /// Evaluates the members of a combinator, each against its own slice of the member states.
+    /// Only the member at `only` is evaluated if given. Returns a bitmask of the members that split.
+    fn member_splits(
+        &self,
+        member_states: &mut [SplitState],
+        split: SplitType,
+        route: &Route,
+        only: Option<usize>,
+    ) -> u32 {
+        let mut splits = 0;
+        let mut offset = 0;
+        for (i, member) in route.members(split).iter().enumerate() {
+            let count = route.state_count(*member);
+            let Some(states) = member_states.get_mut(offset..offset + count) else {
+                break;
+            };
+            offset += count;
+            if only.is_none_or(|only| only == i) && self.should_split(states, *member, route) {
+                splits |= 1 << i;
+            }
+        }
+        splits
+    }
+    /// Returns true if the exp gained was exactly equivalent to a split type.
+    /// The first split state is for the split itself, followed by the states of any combinator members.
+    pub fn should_split(
+        &self,
+        split_states: &mut [SplitState],
+        split: SplitType,
+        route: &Route,
+    ) -> bool {
+        let Some((split_state, member_states)) = split_states.split_first_mut() else {
+            return false;
+        };
+        // Conditions can compare `valid` and the difficulty themselves, so they are not held to either
+        if let SplitType::Custom { index } = split {
+            if let (true, Some(diff), Some(old), Some(current)) = (
+                self.valid.current,
+                self.difficulty.current,
+                self.exps.old,
+                self.exps.current,
+            ) {
+                self.record_events(split_state, diff, current - old);
+            }
+            return route.condition(index).is_some_and(|condition| {
+                condition.evaluate(|field| self.field_value(field, split_state))
+            });
+        }
         if !self.valid.current {
             false
         } else if let Some(diff) = self.difficulty.current {
@@ -237,6 +648,7 @@ impl StateChange {
                 (Some(old), Some(current)) => {
                     let exp_difference = current - old;
                     let raw_level_change = self.levels.old != self.levels.current;
+                    self.record_events(split_state, diff, exp_difference);
                     // TODO: For some of these, we could try doing EITHER an exp change OR tracking the level from the beginning.
                     // If we track from the beginning, things are cleaner, but that may not always be possible.
                     // For example, we could be practicing some splits, or we may have opened the timer in the middle of a game.
@@ -263,41 +675,70 @@ impl StateChange {
                         | SplitType::Odin
                         | SplitType::Diablo
                         | SplitType::CowLevel => {
-                            (split.is_boss_level() && split.boss_exp(diff) == exp_difference)
+                            split.boss_exp(diff) == Some(exp_difference)
                                 || (raw_level_change && self.levels.old == split)
                         }
                         SplitType::Bot2000Cube => {
                             self.levels.current == SplitType::Bot2000
-                                && exp_difference == SplitType::Bot2000.cube_exp(diff)
+                                && Some(exp_difference) == SplitType::Bot2000.cube_exp(diff)
                         }
                         SplitType::OdinCube => {
                             self.levels.current == SplitType::Odin
-                                && exp_difference == SplitType::Odin.cube_exp(diff)
+                                && Some(exp_difference) == SplitType::Odin.cube_exp(diff)
                         }
                         SplitType::PadCrossed => self.pads.old != self.pads.current,
                         // Discriminated types
                         SplitType::PadCrossedForLevel { raw_level } => {
-                            self.levels.old == SplitType::from_raw_level(raw_level)
+                            Some(self.levels.old) == SplitType::from_raw_level(raw_level)
                                 && self.pads.old != self.pads.current
                         }
                         SplitType::CompleteForLevel { raw_level } => {
                             raw_level_change
-                                && self.levels.old == SplitType::from_raw_level(raw_level)
+                                && Some(self.levels.old) == SplitType::from_raw_level(raw_level)
                         }
-                        SplitType::PadsCrossed { .. } => {
-                            if self.pads.old != self.pads.current {
-                                split_state.visit_pad();
-                            }
-                            split_state.pads() == 0
+                        SplitType::PadCrossedForLevelOnLoop {
+                            raw_level,
+                            loop_num,
+                        } => {
+                            self.loops.old == loop_num
+                                && Some(self.levels.old) == SplitType::from_raw_level(raw_level)
+                                && self.pads.old != self.pads.current
+                        }
+                        SplitType::CompleteForLevelOnLoop {
+                            raw_level,
+                            loop_num,
+                        } => {
+                            raw_level_change
+                                && self.loops.old == loop_num
+                                && Some(self.levels.old) == SplitType::from_raw_level(raw_level)
+                        }
+                        // Counted splits, the events are recorded above
+                        SplitType::PadsCrossed { num } => split_state.count(EventKind::Pad) >= num,
+                        SplitType::EnergyCubes { num } => split_state.count(EventKind::Cube) >= num,
+                        SplitType::Events { event, num } => split_state.count(event) >= num,
+                        // Evaluated above
+                        SplitType::Custom { .. } => false,
+                        SplitType::AllOf { .. } => {
+                            split_state.split_members(self.member_splits(
+                                member_states,
+                                split,
+                                route,
+                                None,
+                            ));
+                            split_state.members_split().count_ones() as usize
+                                == route.members(split).len()
+                        }
+                        SplitType::AnyOf { .. } => {
+                            self.member_splits(member_states, split, route, None) != 0
                         }
-                        SplitType::EnergyCubes { .. } => {
-                            if (self.levels.current == SplitType::Bot2000
-                                || self.levels.current == SplitType::Odin)
-                                && exp_difference == self.levels.current.cube_exp(diff)
+                        SplitType::Sequence { .. } => {
+                            let progress = split_state.progress();
+                            // Only one member can split per update, so a single change never completes two steps
+                            if self.member_splits(member_states, split, route, Some(progress)) != 0
                             {
-                                split_state.place_cube();
+                                split_state.advance();
                             }
-                            split_state.cubes() == 0
+                            split_state.progress() >= route.members(split).len()
                         }
                     }
                 }
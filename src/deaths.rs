@@ -0,0 +1,75 @@
+use asr::{Address, Process};
+
+use crate::log;
+
+/// Where the alive flag of the local runling is thought to be kept, from the exp: the value right after the exp
+/// signature, 1 while the runling is alive and 0 while it waits to be revived by the team. This has not been checked
+/// against SC2 or the map's trigger data, so deaths are only followed when the experimental setting asks for it.
+pub const ALIVE_OFFSET: u64 = 0x14;
+
+/// A change in whether the runling is alive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeathEvent {
+    /// The runling died
+    Died,
+    /// The team revived the runling after it died
+    Revived,
+}
+
+/// Follows the alive flag of the runling, which sits at a fixed place after the exp.
+/// Anything other than 0 or 1 there means it is not the alive flag, so deaths are not followed for that game.
+/// A tracker made with `disabled` never reads anything.
+pub struct DeathTracker<'a> {
+    process: &'a Process,
+    alive_pointer: Option<Address>,
+    alive: Option<bool>,
+    /// Whether the runling died since the flag was first read, so that spawning when the game loads is not a revive
+    died: bool,
+}
+
+impl<'a> DeathTracker<'a> {
+    pub fn new(process: &'a Process, exp_pointer: Address) -> DeathTracker<'a> {
+        Self {
+            process,
+            alive_pointer: Some(exp_pointer + ALIVE_OFFSET),
+            alive: None,
+            died: false,
+        }
+    }
+    pub fn disabled(process: &'a Process) -> DeathTracker<'a> {
+        Self {
+            process,
+            alive_pointer: None,
+            alive: None,
+            died: false,
+        }
+    }
+}
+
+impl DeathTracker<'_> {
+    /// Reads the alive flag, returning whether the runling just died or was revived
+    pub fn update(&mut self) -> Option<DeathEvent> {
+        let pointer = self.alive_pointer?;
+        let alive = match self.process.read::<u32>(pointer) {
+            Ok(0) => false,
+            Ok(1) => true,
+            Ok(value) => {
+                log!("Not counting deaths because the alive flag was read as: {value}!");
+                self.alive_pointer = None;
+                self.alive = None;
+                return None;
+            }
+            // The exp read notices the game going away, so this is left to it
+            Err(_) => return None,
+        };
+        let old_alive = self.alive.replace(alive)?;
+        match (old_alive, alive) {
+            (true, false) => {
+                self.died = true;
+                Some(DeathEvent::Died)
+            }
+            (false, true) if self.died => Some(DeathEvent::Revived),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use asr::{settings::Gui, time::Duration, time_util::Instant, Address, Process};
+
+use crate::log;
+
+/// Size of a page, which the window around the exp is read in
+const PAGE_SIZE: usize = 4 << 10;
+/// Pages watched for the game loop, starting from the page before the one with the exp
+const WINDOW_PAGES: usize = 4;
+const WINDOW_WORDS: usize = WINDOW_PAGES * PAGE_SIZE / 4;
+/// Real time the window is watched for before picking out the game loop
+const CALIBRATION_TIME: Duration = Duration::seconds(3);
+/// Most the game loop can advance by between two ticks, allowing for ticks that come late
+const MAX_LOOP_STEP: u32 = 8;
+/// How far off the rate of a game speed a counter can advance during calibration and still be taken for the game
+/// loop. Over the calibration time, a step of the game loop at the Slower speed is under 4% of its rate, and the
+/// closest game speeds are 15% apart.
+const CALIBRATION_TOLERANCE: f64 = 0.06;
+/// Steps of the game loop it has to stand still for before the game counts as paused. A game that stutters for that
+/// long does not advance either, so it can just as well be left out of game time.
+const PAUSE_LOOPS: f64 = 3.0;
+/// Unpaused real time the game loop is timed over to tell the game speed, and then over again each time after, to
+/// notice it being changed in the options. The closest game speeds are 15% apart, so this needs to be long enough for
+/// a step of the game loop and a late tick not to matter.
+const SPEED_TIME: Duration = Duration::seconds(5);
+
+/// How fast the game was set to run in the lobby or the options
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameSpeed {
+    Slower,
+    Slow,
+    Normal,
+    Fast,
+    Faster,
+}
+
+impl GameSpeed {
+    const ALL: [GameSpeed; 5] = [
+        GameSpeed::Slower,
+        GameSpeed::Slow,
+        GameSpeed::Normal,
+        GameSpeed::Fast,
+        GameSpeed::Faster,
+    ];
+    /// Returns the game loops per real second. The game runs at 16 loops per game second, and the game speed is how
+    /// many game seconds pass in a real second.
+    pub fn loops_per_second(&self) -> f64 {
+        16.0 * match self {
+            GameSpeed::Slower => 0.6,
+            GameSpeed::Slow => 0.8,
+            GameSpeed::Normal => 1.0,
+            GameSpeed::Fast => 1.2,
+            GameSpeed::Faster => 1.4,
+        }
+    }
+    /// Returns the game speed whose rate of game loops the one given is within the calibration tolerance of
+    fn matching(loops_per_second: f64) -> Option<Self> {
+        let speed = Self::closest(loops_per_second);
+        let difference = loops_per_second / speed.loops_per_second() - 1.0;
+        (-CALIBRATION_TOLERANCE..=CALIBRATION_TOLERANCE)
+            .contains(&difference)
+            .then_some(speed)
+    }
+    /// Returns how long the game loop takes to advance by the steps at this speed
+    fn steps_duration(&self, steps: f64) -> Duration {
+        Duration::seconds_f64(steps / self.loops_per_second())
+    }
+    /// Returns the game speed with the closest rate of game loops to the one given
+    fn closest(loops_per_second: f64) -> Self {
+        let distance = |speed: &GameSpeed| {
+            let difference = speed.loops_per_second() - loops_per_second;
+            if difference < 0.0 {
+                -difference
+            } else {
+                difference
+            }
+        };
+        let mut closest = GameSpeed::Normal;
+        for speed in GameSpeed::ALL {
+            if distance(&speed) < distance(&closest) {
+                closest = speed;
+            }
+        }
+        closest
+    }
+    /// Returns the real time that the game loops take at this speed
+    pub fn loops_to_duration(&self, loops: u32) -> Duration {
+        Duration::seconds_f64(loops as f64 / self.loops_per_second())
+    }
+}
+
+/// What game time is set from
+#[derive(Gui, Debug, Clone, Copy, PartialEq)]
+pub enum GameTimeSource {
+    /// Real time, paused while SC2 is paused
+    #[default]
+    RealTime,
+    /// The game loop, converted to real time at the game speed
+    GameLoop,
+}
+
+/// A change in whether the game is running, which game time should follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockEvent {
+    /// The game loop stopped, because the game was paused or is at a dialog
+    Paused,
+    /// The game loop is advancing again
+    Resumed,
+}
+
+/// Follows the game loop, the counter that SC2 advances on every step of the game and stops while it is paused.
+/// Where the game loop is kept is not known ahead of time, so it is found by watching the memory around the exp for
+/// a counter that only ever advances, at the rate of one of the game speeds against real time. Other counters that
+/// happen to advance at such a rate cannot be told apart from it, so the game loop is only taken if it is the only
+/// one, or the others are copies of it.
+pub struct GameClock<'a> {
+    process: &'a Process,
+    window_start: Address,
+    /// The window as it was when calibration started, and as it was last read
+    first_words: [u32; WINDOW_WORDS],
+    last_words: [u32; WINDOW_WORDS],
+    /// Words that have only counted up so far during calibration
+    candidates: [bool; WINDOW_WORDS],
+    /// When calibration started, unless it is about to
+    calibration_start: Option<Instant>,
+    calibration_failures: u32,
+    game_loop_pointer: Option<Address>,
+    game_loop: Option<u32>,
+    /// When the game loop last advanced, and how long it has to stand still for to count as paused
+    last_step: Instant,
+    pause_time: Duration,
+    paused: bool,
+    /// The game loop the game speed is being timed from, and when it was read
+    speed_start: Option<(u32, Instant)>,
+    game_speed: Option<GameSpeed>,
+}
+
+impl<'a> GameClock<'a> {
+    pub fn new(process: &'a Process, exp_pointer: Address) -> GameClock<'a> {
+        let exp_page = exp_pointer.value() & !(PAGE_SIZE as u64 - 1);
+        Self {
+            process,
+            window_start: Address::new(exp_page.saturating_sub(PAGE_SIZE as u64)),
+            first_words: [0; WINDOW_WORDS],
+            last_words: [0; WINDOW_WORDS],
+            candidates: [false; WINDOW_WORDS],
+            calibration_start: None,
+            calibration_failures: 0,
+            game_loop_pointer: None,
+            game_loop: None,
+            last_step: Instant::now(),
+            pause_time: Duration::ZERO,
+            paused: false,
+            speed_start: None,
+            game_speed: None,
+        }
+    }
+}
+
+impl GameClock<'_> {
+    /// Returns the last game loop that was read, once the game loop has been found
+    pub fn game_loop(&self) -> Option<u32> {
+        self.game_loop
+    }
+    /// Returns the game speed, once the game loop has been timed for long enough to tell
+    pub fn game_speed(&self) -> Option<GameSpeed> {
+        self.game_speed
+    }
+    /// Reads the window into the words, returning a bitmask of the pages that could be read
+    fn read_window(&self, words: &mut [u32; WINDOW_WORDS]) -> u32 {
+        let mut readable = 0;
+        let mut page = [0u8; PAGE_SIZE];
+        for (i, page_words) in words.chunks_exact_mut(PAGE_SIZE / 4).enumerate() {
+            let address = self.window_start + (i * PAGE_SIZE) as u64;
+            if self.process.read_into_buf(address, &mut page).is_err() {
+                continue;
+            }
+            readable |= 1 << i;
+            for (word, bytes) in page_words.iter_mut().zip(page.chunks_exact(4)) {
+                *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+        }
+        readable
+    }
+    /// Watches the window for one more tick, picking out the game loop once calibration is over
+    fn calibrate(&mut self) {
+        let mut words = [0; WINDOW_WORDS];
+        let readable = self.read_window(&mut words);
+        let page_readable = |i: usize| readable & (1 << (i / (PAGE_SIZE / 4))) != 0;
+        let Some(start) = self.calibration_start else {
+            self.calibration_start = Some(Instant::now());
+            self.first_words = words;
+            self.last_words = words;
+            for (i, candidate) in self.candidates.iter_mut().enumerate() {
+                *candidate = page_readable(i);
+            }
+            return;
+        };
+        for (i, candidate) in self.candidates.iter_mut().enumerate() {
+            let last = self.last_words[i];
+            *candidate &= page_readable(i) && words[i] >= last && words[i] - last <= MAX_LOOP_STEP;
+        }
+        self.last_words = words;
+        let elapsed = start.elapsed();
+        if elapsed < CALIBRATION_TIME {
+            return;
+        }
+        self.calibration_start = None;
+        let mut found: Option<(usize, GameSpeed)> = None;
+        let mut ambiguous = false;
+        for i in (0..WINDOW_WORDS).filter(|i| self.candidates[*i]) {
+            let loops_per_second =
+                (words[i] - self.first_words[i]) as f64 / elapsed.as_seconds_f64();
+            let Some(game_speed) = GameSpeed::matching(loops_per_second) else {
+                continue;
+            };
+            match found {
+                None => found = Some((i, game_speed)),
+                // A copy of the game loop has the same value, anything else could be the game loop just as well
+                Some((first, _)) => ambiguous |= words[i] != words[first],
+            }
+        }
+        match found {
+            Some((i, game_speed)) if !ambiguous => {
+                let pointer = self.window_start + (i * 4) as u64;
+                log!("Found the game loop at: {pointer:?}, advancing like at the {game_speed:?} game speed");
+                self.game_loop_pointer = Some(pointer);
+                self.game_loop = Some(words[i]);
+                self.last_step = Instant::now();
+                self.pause_time = game_speed.steps_duration(PAUSE_LOOPS);
+                self.time_speed_from(words[i]);
+            }
+            _ => {
+                // This keeps failing while the game is paused, so it is only worth saying the first time
+                self.calibration_failures += 1;
+                if self.calibration_failures == 1 {
+                    match ambiguous {
+                        true => log!("Found several counters near the exp that could be the game loop! Still watching!"),
+                        false => log!("Could not find the game loop near the exp, is the game paused? Still watching!"),
+                    }
+                }
+            }
+        }
+    }
+    /// Starts timing the game loop over from the given loop, to tell the game speed
+    fn time_speed_from(&mut self, game_loop: u32) {
+        self.speed_start = Some((game_loop, Instant::now()));
+    }
+    /// Times the game loop up to a loop it just advanced to, telling the game speed once it has been timed for long
+    /// enough. Real time is measured rather than counted in ticks, since the runtime does not keep to the tick rate.
+    /// Where SC2 keeps the game speed is not known, so it keeps being timed to notice it being changed.
+    fn time_speed(&mut self, game_loop: u32) {
+        let Some((start_loop, start)) = self.speed_start else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        if elapsed < SPEED_TIME {
+            return;
+        }
+        let loops_per_second = game_loop.wrapping_sub(start_loop) as f64 / elapsed.as_seconds_f64();
+        let game_speed = GameSpeed::closest(loops_per_second);
+        self.time_speed_from(game_loop);
+        match self.game_speed {
+            Some(old) if old == game_speed => return,
+            Some(old) => log!(
+                "Timed the game loop at {loops_per_second:.1} loops per second, so the game speed changed from: {old:?} to: {game_speed:?}"
+            ),
+            None => log!("Timed the game loop at {loops_per_second:.1} loops per second, so the game speed is: {game_speed:?}"),
+        }
+        self.game_speed = Some(game_speed);
+        self.pause_time = game_speed.steps_duration(PAUSE_LOOPS);
+    }
+    /// Forgets the game loop and starts looking for it again, resuming if it was paused
+    fn recalibrate(&mut self) -> Option<ClockEvent> {
+        self.game_loop_pointer = None;
+        self.game_loop = None;
+        self.calibration_start = None;
+        self.calibration_failures = 0;
+        core::mem::replace(&mut self.paused, false).then_some(ClockEvent::Resumed)
+    }
+    /// Reads the game loop, returning whether the game was just paused or resumed
+    pub fn update(&mut self) -> Option<ClockEvent> {
+        let Some(pointer) = self.game_loop_pointer else {
+            self.calibrate();
+            return None;
+        };
+        let game_loop = match self.process.read::<u32>(pointer) {
+            Ok(game_loop) => game_loop,
+            Err(_) => {
+                log!("Lost the game loop because it could not be read!");
+                return self.recalibrate();
+            }
+        };
+        match self.game_loop {
+            Some(old) if game_loop < old => {
+                log!("Lost the game loop because it went back from: {old} to: {game_loop}!");
+                self.recalibrate()
+            }
+            Some(old) if game_loop == old => {
+                if !self.paused && self.last_step.elapsed() >= self.pause_time {
+                    self.paused = true;
+                    return Some(ClockEvent::Paused);
+                }
+                None
+            }
+            _ => {
+                self.game_loop = Some(game_loop);
+                self.last_step = Instant::now();
+                if core::mem::replace(&mut self.paused, false) {
+                    // Time the speed from here, leaving out the pause and the time before it was noticed
+                    self.time_speed_from(game_loop);
+                    return Some(ClockEvent::Resumed);
+                }
+                self.time_speed(game_loop);
+                None
+            }
+        }
+    }
+}
+
+/// Game time told from the game loop, counted from the loop the timer was started at. A run that is started partway
+/// into a game, such as an IL, or that is carried on in another game, is then timed from where it was at.
+#[derive(Debug, Default)]
+pub struct LoopTime {
+    /// Game time at the base loop, and the base loop, which is the first loop read after if it was not known yet
+    base_time: Duration,
+    base_loop: Option<u32>,
+    /// The game time last told, if any was told since the splitter started
+    time: Option<Duration>,
+    /// The loop and game speed game time was last told at, in this game
+    last: Option<(u32, GameSpeed)>,
+}
+
+impl LoopTime {
+    /// Returns the game time last told, if any was told since the splitter started
+    pub fn time(&self) -> Option<Duration> {
+        self.time
+    }
+    /// Counts game time from zero at the loop
+    pub fn start(&mut self, game_loop: Option<u32>) {
+        *self = Self {
+            base_time: Duration::ZERO,
+            base_loop: game_loop,
+            time: Some(Duration::ZERO),
+            last: None,
+        };
+    }
+    /// Carries on counting at the loop, from the game time last told, or from the game time given if none was told
+    /// since the splitter started
+    pub fn carry_on(&mut self, game_loop: Option<u32>, time: Option<Duration>) {
+        self.base_time = self.time.or(time).unwrap_or_default();
+        self.base_loop = game_loop;
+        self.last = None;
+    }
+    /// Returns the game time at the loop, once the game loop and game speed are known. A change of game speed counts
+    /// from the loop game time was last told at, keeping the game time up to there.
+    pub fn time_at(
+        &mut self,
+        game_loop: Option<u32>,
+        game_speed: Option<GameSpeed>,
+    ) -> Option<Duration> {
+        let game_loop = game_loop?;
+        let base_loop = *self.base_loop.get_or_insert(game_loop);
+        let game_speed = game_speed?;
+        let (base_time, base_loop) = match (self.last, self.time) {
+            (Some((last_loop, last_speed)), Some(time)) if last_speed != game_speed => {
+                self.base_time = time;
+                self.base_loop = Some(last_loop);
+                (time, last_loop)
+            }
+            _ => (self.base_time, base_loop),
+        };
+        let time = base_time + game_speed.loops_to_duration(game_loop.saturating_sub(base_loop));
+        self.time = Some(time);
+        self.last = Some((game_loop, game_speed));
+        Some(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loops at the Normal game speed that take a second
+    const SECOND: u32 = 16;
+
+    #[test]
+    fn only_game_speed_rates_match() {
+        assert_eq!(GameSpeed::matching(22.4), Some(GameSpeed::Faster));
+        assert_eq!(GameSpeed::matching(9.9), Some(GameSpeed::Slower));
+        assert_eq!(GameSpeed::matching(17.6), None);
+        // A frame counter, or a second counter
+        assert_eq!(GameSpeed::matching(30.0), None);
+        assert_eq!(GameSpeed::matching(1.0), None);
+    }
+
+    #[test]
+    fn loop_time_counts_from_the_start() {
+        let mut loop_time = LoopTime::default();
+        loop_time.start(Some(10 * SECOND));
+        assert_eq!(
+            loop_time.time_at(Some(12 * SECOND), Some(GameSpeed::Normal)),
+            Some(Duration::seconds(2))
+        );
+    }
+
+    #[test]
+    fn loop_time_counts_from_the_first_loop_read() {
+        let mut loop_time = LoopTime::default();
+        loop_time.start(None);
+        assert_eq!(loop_time.time_at(Some(5 * SECOND), None), None);
+        assert_eq!(
+            loop_time.time_at(Some(8 * SECOND), Some(GameSpeed::Normal)),
+            Some(Duration::seconds(3))
+        );
+    }
+
+    #[test]
+    fn loop_time_keeps_the_time_before_the_game_speed_changed() {
+        let mut loop_time = LoopTime::default();
+        loop_time.start(Some(0));
+        loop_time.time_at(Some(10 * SECOND), Some(GameSpeed::Normal));
+        // Faster runs 1.4 times as many loops in a second
+        assert_eq!(
+            loop_time.time_at(Some(10 * SECOND + 112), Some(GameSpeed::Faster)),
+            Some(Duration::seconds(15))
+        );
+        assert_eq!(
+            loop_time.time_at(Some(10 * SECOND + 224), Some(GameSpeed::Faster)),
+            Some(Duration::seconds(20))
+        );
+    }
+
+    #[test]
+    fn loop_time_carries_on() {
+        let mut loop_time = LoopTime::default();
+        loop_time.start(Some(0));
+        loop_time.time_at(Some(30 * SECOND), Some(GameSpeed::Normal));
+        // Another game starts from loop 0 again
+        loop_time.carry_on(Some(0), Some(Duration::seconds(10)));
+        assert_eq!(
+            loop_time.time_at(Some(5 * SECOND), Some(GameSpeed::Normal)),
+            Some(Duration::seconds(35))
+        );
+        // Without any game time told, such as after the splitter restarted, the given game time is carried on from
+        let mut loop_time = LoopTime::default();
+        loop_time.carry_on(Some(0), Some(Duration::seconds(10)));
+        assert_eq!(
+            loop_time.time_at(Some(5 * SECOND), Some(GameSpeed::Normal)),
+            Some(Duration::seconds(15))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use asr::settings::Gui;
+
+use crate::{data::StateChange, log, route::Route, split_state::SplitState, split_type::SplitType};
+
+#[derive(Gui, Debug, Clone, Copy, PartialEq)]
+pub enum IlLevel {
+    /// Level 1
+    #[default]
+    Level1,
+    /// Level 2
+    Level2,
+    /// Level 3
+    Level3,
+    /// Bot2000
+    Bot2000,
+    /// Level 4
+    Level4,
+    /// Level 5
+    Level5,
+    /// Level 6
+    Level6,
+    /// Odin
+    Odin,
+    /// Level 7
+    Level7,
+    /// Level 8
+    Level8,
+    /// Level 9
+    Level9,
+    /// Diablo
+    Diablo,
+    /// Cow Level
+    CowLevel,
+}
+
+impl IlLevel {
+    pub fn split_type(&self) -> SplitType {
+        match self {
+            IlLevel::Level1 => SplitType::Level1,
+            IlLevel::Level2 => SplitType::Level2,
+            IlLevel::Level3 => SplitType::Level3,
+            IlLevel::Bot2000 => SplitType::Bot2000,
+            IlLevel::Level4 => SplitType::Level4,
+            IlLevel::Level5 => SplitType::Level5,
+            IlLevel::Level6 => SplitType::Level6,
+            IlLevel::Odin => SplitType::Odin,
+            IlLevel::Level7 => SplitType::Level7,
+            IlLevel::Level8 => SplitType::Level8,
+            IlLevel::Level9 => SplitType::Level9,
+            IlLevel::Diablo => SplitType::Diablo,
+            IlLevel::CowLevel => SplitType::CowLevel,
+        }
+    }
+}
+
+/// What the timer should do in response to an update while in IL mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IlAction {
+    /// The level was (re)started, so the timer should be reset and started
+    Start,
+    /// A pad was crossed or the level was completed
+    Split,
+    /// The game was left or restarted with the level in progress, so the timer should be reset
+    Reset,
+}
+
+/// State machine for timing a single level instead of the full run
+pub struct IlRun {
+    level: SplitType,
+    split_pads: bool,
+    running: bool,
+    split_state: SplitState,
+}
+
+impl IlRun {
+    pub fn new(level: SplitType, split_pads: bool) -> Self {
+        Self {
+            level,
+            split_pads,
+            running: false,
+            split_state: SplitState::default(),
+        }
+    }
+    /// Returns what the timer should do now that tracking was lost, which it is when the game is left or restarted. A
+    /// level in progress is reset, and is timed again once it starts over in the next game.
+    pub fn tracking_lost(&mut self) -> Option<IlAction> {
+        core::mem::replace(&mut self.running, false).then_some(IlAction::Reset)
+    }
+    /// Advances the state machine with the latest state change, returning what the timer should do, if anything
+    pub fn update(&mut self, state: &StateChange, route: &Route) -> Option<IlAction> {
+        // IL levels are always levels, so this always has a raw level
+        let raw_level = self.level.raw_level()?;
+        if !self.running {
+            if state.started_level(self.level) {
+                log!("IL started for: {:?}", self.level);
+                self.running = true;
+                return Some(IlAction::Start);
+            }
+            return None;
+        }
+        // Completion also counts as a pad change, so check for it first
+        if state.should_split(
+            core::slice::from_mut(&mut self.split_state),
+            SplitType::CompleteForLevel { raw_level },
+            route,
+        ) {
+            log!("IL complete for: {:?}", self.level);
+            self.running = false;
+            Some(IlAction::Split)
+        } else if self.split_pads
+            && state.should_split(
+                core::slice::from_mut(&mut self.split_state),
+                SplitType::PadCrossedForLevel { raw_level },
+                route,
+            )
+        {
+            Some(IlAction::Split)
+        } else {
+            None
+        }
+    }
+}
@@ -1,21 +1,38 @@
 #![feature(type_alias_impl_trait, const_async_blocks)]
 #![feature(portable_simd)]
 #![feature(array_into_iter_constructors)]
-#![feature(maybe_uninit_array_assume_init)]
 #![cfg(target_feature = "simd128")]
 #![no_std]
 
+mod attach;
+mod condition;
 mod data;
+mod deaths;
+mod game_clock;
+mod il;
+mod lifecycle;
+mod progress;
+mod route;
 mod sigscan;
 mod split_state;
 mod split_type;
+mod timer_sync;
 
+use core::fmt::Write;
+
+use asr::arrayvec::ArrayString;
 use asr::time::Duration;
-use asr::Process;
+use asr::timer::TimerState;
 use asr::{future::next_tick, settings::Gui, timer};
-use data::GameData;
+use attach::ProcessPreference;
+use data::{GameData, StateChange};
+use game_clock::{ClockEvent, GameTimeSource, LoopTime};
+use il::{IlAction, IlLevel, IlRun};
+use lifecycle::{GameState, Lifecycle, LifecycleEvent};
+use progress::Progress;
+use route::{Category, Route};
 use split_state::SplitState;
-use split_type::SplitType;
+use timer_sync::{TimerEvent, TimerSync};
 
 asr::async_main!(nightly);
 asr::panic_handler!();
@@ -63,15 +80,54 @@ macro_rules! dbg {
 
 #[derive(Gui, Debug)]
 struct Settings {
-    /// Automattcally reset the timer when RLR4 has ended
+    /// Automatically reset the timer when a new game of RLR4 is started
     #[default = false]
     auto_reset: bool,
     /// Automatically set the game time in livesplit to match the IGT in game
     #[default = false]
     set_game_time: bool,
+    /// Experimental: follow the game loop, told apart from other counters near the exp by how fast it advances, which
+    /// is not verified against SC2. Game time is then paused while SC2 is paused, or set from the game loop
+    #[default = false]
+    follow_game_loop: bool,
+    /// What game time is set from when following the game loop: real time paused along with SC2, or the game loop at
+    /// the game speed
+    game_time_source: GameTimeSource,
     /// Automatically start the timer
     #[default = false]
     auto_start: bool,
+    /// Category to split for
+    category: Category,
+    /// Individual level mode: start, split and reset on a single level instead of the full run
+    #[default = false]
+    il_mode: bool,
+    /// Level to time in individual level mode
+    il_level: IlLevel,
+    /// Split on every pad of the level in individual level mode
+    #[default = false]
+    il_split_pads: bool,
+    /// Games start on the level in individual level mode, such as when practicing it, rather than on Level 1
+    #[default = false]
+    il_start_at_level: bool,
+    /// Experimental: track deaths from where the alive flag is thought to be, which is not verified against SC2
+    #[default = false]
+    track_deaths: bool,
+    /// Resume the run from the last split when SC2 or the auto splitter restarts mid-run, if the exp still matches
+    #[default = false]
+    resume_runs: bool,
+    /// SC2 process to prefer when several are running and none of their exp changes, by process id
+    process_preference: ProcessPreference,
+}
+
+impl Settings {
+    /// Returns what game time is set from, if it is set. Without the game loop, it is real time.
+    fn game_time_source(&self) -> Option<GameTimeSource> {
+        match (self.set_game_time, self.follow_game_loop) {
+            (false, _) => None,
+            (true, false) => Some(GameTimeSource::RealTime),
+            (true, true) => Some(self.game_time_source),
+        }
+    }
 }
 
 async fn main() {
@@ -79,26 +135,20 @@ async fn main() {
 
     log!("Loaded settings: {settings:?}");
     asr::set_tick_rate(30.0);
-
-    // TODO: Back with a settings structure of some kind
-    let all_splits = [
-        SplitType::Level1,
-        SplitType::Level2,
-        SplitType::Level3,
-        SplitType::Bot2000,
-        SplitType::Level4,
-        SplitType::Level5,
-        SplitType::Level6,
-        SplitType::Odin,
-        SplitType::Level7,
-        SplitType::Level8,
-        SplitType::Level9,
-        SplitType::Diablo,
-    ];
-    let mut split_iter = all_splits.iter();
+    let mut lifecycle = Lifecycle::default();
+    // The last exp above 0 that was seen, which is a guess at what the bank was saved with, since it is saved
+    // whenever the exp changes
+    let mut last_seen_exp = None;
+    // Whether the timer was paused by leaving the game, so that it is only carried on with by the next game then
+    let mut paused_for_left_game = false;
+    // Game time from the game loop, which is kept across games and SC2 restarts for a run that is carried on
+    let mut loop_time = LoopTime::default();
 
     loop {
-        let process = Process::wait_attach("SC2_x64.exe").await;
+        settings.update();
+        let (process, exp_pointer) = attach::wait_attach(settings.process_preference).await;
+        // The exp was already found while choosing the process, so the first scan can be skipped
+        let mut exp_pointer = Some(exp_pointer);
         process
             .until_closes(async {
                 log!("Attached to process!");
@@ -109,36 +159,151 @@ async fn main() {
                         timer::set_game_time(Duration::ZERO);
                         // TODO: Figure out how to set game_time within the critical loop to something from the game
                     }
+                    // Tracking is invalidated within a game too, which only scans for the same exp struct again
+                    if !lifecycle.state().in_game() {
+                        lifecycle.update(GameState::Menu);
+                    }
                     // Try to make a gamedata instance
-                    let mut data = GameData::new(&process).await;
+                    let mut data = match exp_pointer.take() {
+                        Some(exp_pointer) => GameData::with_exp_pointer(&process, exp_pointer),
+                        None => GameData::new(&process).await,
+                    };
+                    // The bank keeps the exp of the last game, so loading it again is not mistaken for a gain
+                    if let Some(exp) = last_seen_exp {
+                        data.remember_exp(exp);
+                    }
                     // Set tick rate back to something fast enough to catch cases
                     asr::set_tick_rate(120.0);
                     settings.update();
                     // Now that we have a game data instance, first immediately try to start the timer as needed
-                    if settings.set_game_time {
+                    // Game time set from the game loop only moves when it is set, so it stays paused
+                    if settings.game_time_source() == Some(GameTimeSource::RealTime) {
                         log!("RESUMING GAME TIME");
                         timer::resume_game_time();
                     }
-                    if settings.auto_start {
-                        log!("STARTING THE TIMER!");
-                        timer::start();
-                    }
-                    // When we reset, we reset counting the splits
-                    split_iter = all_splits.iter();
-                    let mut split = split_iter.next();
+                    // When we reset, we reset counting the splits, picking up any category change
+                    let route = Route::load(settings.category);
+                    let mut split_index = 0;
+                    let mut split = route.splits().get(split_index);
                     // Form the split state with the options from this current split, if present.
-                    let mut split_state = SplitState::from_split(split);
+                    let mut split_state = SplitState::from_split(split, &route);
+                    if settings.il_mode && settings.il_start_at_level {
+                        data.start_at(settings.il_level.split_type());
+                    }
+                    if settings.follow_game_loop {
+                        data.follow_game_loop();
+                    }
+                    if settings.track_deaths {
+                        data.track_deaths();
+                    }
+                    let mut il_run =
+                        IlRun::new(settings.il_level.split_type(), settings.il_split_pads);
+                    show_deaths(&data);
+                    let mut timer_sync = TimerSync::default();
                     // TODO: Depending on if our run type has a set difficulty or not, force a certain difficulty instead of deducing it
-                    // Here and also within the split_iter.next() call
+                    // Here and also when moving on to the next split
                     loop {
                         settings.update();
+                        // Follow any splits, skips, undos or resets done by hand in LiveSplit.
+                        // IL mode drives the timer itself, so there is no route to follow.
+                        let event = match settings.il_mode {
+                            true => None,
+                            false => timer_sync.update(&route, split_index),
+                        };
+                        let moved_to = match event {
+                            Some(TimerEvent::Reset) => {
+                                log!("THE TIMER WAS RESET, STARTING THE ROUTE OVER!");
+                                data.reset_tracking();
+                                show_deaths(&data);
+                                Progress::clear();
+                                Some(0)
+                            }
+                            Some(TimerEvent::MovedTo(index)) => {
+                                log!("FOLLOWING LIVESPLIT TO SEGMENT: {}", index + 1);
+                                Some(index)
+                            }
+                            None => None,
+                        };
+                        if let Some(index) = moved_to {
+                            split_index = index;
+                            split = route.splits().get(split_index);
+                            split_state = SplitState::from_split(split, &route);
+                            store_progress(&data, split_index, &route, &loop_time);
+                        }
                         // General loop consists of performing an exp update
                         let state = data.update();
+                        // Follow the game being started, left or won. A run is only reset by starting a new game,
+                        // so leaving the game just pauses it. The game is won once the route is finished, which IL
+                        // mode has no route for. Being inferred from the route, winning is only logged, and the timer
+                        // is not reset for it.
+                        let route_finished = !settings.il_mode && split.is_none();
+                        let lifecycle_event = data
+                            .game_state(route_finished)
+                            .and_then(|game_state| lifecycle.update(game_state));
+                        if state.bank_loaded() {
+                            log!("THE BANK WAS LOADED, THE RUN COUNTS FROM ITS EXP!");
+                        }
+                        if let Some(exp) = data.exp().filter(|exp| *exp > 0) {
+                            last_seen_exp = Some(exp);
+                        }
+                        // Carry on from the last split if SC2 or the splitter restarted during the run. A game that
+                        // starts out loading only has the exp of the run once the bank is loaded.
+                        let mut resumed = false;
+                        // The game loop of a new game starts from zero, even before it has been found
+                        let new_game = lifecycle_event == Some(LifecycleEvent::NewGame);
+                        let game_loop = if new_game { Some(0) } else { state.game_loop() };
+                        if new_game || state.bank_loaded() {
+                            if let Some(progress) = resume_run(&settings, &mut data, &route) {
+                                log!("RESUMING THE RUN FROM: {progress}");
+                                loop_time.carry_on(game_loop, progress.game_time);
+                                split_index = progress.split_index;
+                                split = route.splits().get(split_index);
+                                split_state = SplitState::from_split(split, &route);
+                                show_deaths(&data);
+                                resumed = true;
+                            }
+                        }
+                        match lifecycle_event {
+                            Some(LifecycleEvent::NewGame) => {
+                                let mut run_in_progress = run_in_progress();
+                                if !resumed && settings.auto_reset && run_in_progress {
+                                    log!("A NEW GAME WAS STARTED, RESETTING THE TIMER!");
+                                    timer::reset();
+                                    Progress::clear();
+                                    run_in_progress = false;
+                                }
+                                // In IL mode, the timer is started when the level starts instead
+                                if !resumed
+                                    && settings.auto_start
+                                    && !settings.il_mode
+                                    && !run_in_progress
+                                {
+                                    log!("STARTING THE TIMER!");
+                                    timer::start();
+                                    loop_time.start(game_loop);
+                                }
+                                // Carry on with a run that was paused by leaving the last game, but not with one that
+                                // was paused by hand
+                                if core::mem::take(&mut paused_for_left_game) {
+                                    timer::resume();
+                                    if run_in_progress && !resumed {
+                                        loop_time.carry_on(game_loop, None);
+                                    }
+                                }
+                            }
+                            Some(LifecycleEvent::LeftGame) => {
+                                paused_for_left_game = pause_for_left_game();
+                            }
+                            Some(LifecycleEvent::Won) => log!("THE GAME WAS WON!"),
+                            None => (),
+                        }
                         // Check to see if we invalidated in some way, if so, reset as needed and break to our outer loop
                         if data.invalid() {
-                            if settings.auto_reset {
+                            // Leaving or restarting the level always resets an IL in progress
+                            if settings.il_mode && il_run.tracking_lost() == Some(IlAction::Reset) {
                                 log!("RESETTING THE TIMER!");
                                 timer::reset();
+                                Progress::clear();
                             }
                             if settings.set_game_time {
                                 log!("PAUSING THE GAME TIME!");
@@ -146,17 +311,76 @@ async fn main() {
                             }
                             break;
                         }
-                        // Then check our upcoming split to see if we should split
-                        // TODO: Keep the split info in a settings file somehow
-                        if let Some(spl) = split {
-                            if state.should_split(&mut split_state, *spl) {
+                        if state.deaths_changed() {
+                            show_deaths(&data);
+                        }
+                        match settings.game_time_source() {
+                            // Game time stands still while SC2 is paused or at a dialog
+                            Some(GameTimeSource::RealTime) => match state.clock_event() {
+                                Some(ClockEvent::Paused) => {
+                                    log!("SC2 WAS PAUSED, PAUSING THE GAME TIME!");
+                                    timer::pause_game_time();
+                                }
+                                Some(ClockEvent::Resumed) => {
+                                    log!("SC2 WAS RESUMED, RESUMING THE GAME TIME!");
+                                    timer::resume_game_time();
+                                }
+                                None => (),
+                            },
+                            // Game time is the time of the game loop, once the game speed is known
+                            Some(GameTimeSource::GameLoop) => {
+                                // Until the timer is started, game time counts from the current loop, so that a
+                                // run started by hand is timed from there
+                                if !run_in_progress() {
+                                    loop_time.start(state.game_loop());
+                                } else if let Some(time) =
+                                    loop_time.time_at(state.game_loop(), data.game_speed())
+                                {
+                                    timer::set_game_time(time);
+                                }
+                            }
+                            None => (),
+                        }
+                        if settings.il_mode {
+                            // In IL mode, the IL state machine decides instead of the route
+                            match il_run.update(&state, &route) {
+                                Some(IlAction::Start) => {
+                                    log!("RESTARTING THE TIMER FOR IL!");
+                                    timer::reset();
+                                    timer::start();
+                                    loop_time.start(state.game_loop());
+                                }
+                                Some(IlAction::Split) => {
+                                    log!("SPLITTING FOR IL!");
+                                    split_at_game_loop(&settings, &state, &data, &mut loop_time);
+                                }
+                                // Only losing tracking resets, which is handled above
+                                Some(IlAction::Reset) | None => (),
+                            }
+                        } else if let Some(spl) = split {
+                            // Then check our upcoming split to see if we should split
+                            // TODO: Keep the split info in a settings file somehow
+                            if state.should_split(&mut split_state, *spl, &route) {
                                 log!("SPLITTING FOR: {spl:?}");
-                                timer::split();
-                                split = split_iter.next();
+                                split_at_game_loop(&settings, &state, &data, &mut loop_time);
+                                split_index += 1;
+                                timer_sync.splitter_moved_to(split_index);
+                                split = route.splits().get(split_index);
                                 // Form the next state with the next split options
-                                split_state = SplitState::from_split(split);
+                                split_state = SplitState::from_split(split, &route);
+                                // Save where we are, so the run can be resumed from here
+                                store_progress(&data, split_index, &route, &loop_time);
                             }
                         }
+                        // The bank is saved whenever exp is gained, so the progress is saved along with it, for the
+                        // exp to still match it when the run is resumed
+                        if settings.resume_runs
+                            && !settings.il_mode
+                            && state.gained_exp()
+                            && run_in_progress()
+                        {
+                            store_progress(&data, split_index, &route, &loop_time);
+                        }
 
                         // TODO: At some cadence, decide to rescan and determine if we should reset (or invalidate)
 
@@ -165,8 +389,78 @@ async fn main() {
                 }
             })
             .await;
+        if lifecycle.update(GameState::Closed) == Some(LifecycleEvent::LeftGame) {
+            paused_for_left_game = pause_for_left_game();
+        }
+    }
+}
+
+/// Splits for a change. When game time is set from the game loop, it is set to the loop the change happened by right
+/// before splitting, so the split lands on that loop instead of on whenever the tick noticed it.
+fn split_at_game_loop(
+    settings: &Settings,
+    state: &StateChange,
+    data: &GameData,
+    loop_time: &mut LoopTime,
+) {
+    if settings.game_time_source() == Some(GameTimeSource::GameLoop) {
+        if let Some(time) = loop_time.time_at(state.game_loop(), data.game_speed()) {
+            timer::set_game_time(time);
+        }
     }
+    timer::split();
 }
 
-// TODO: Handle the case where we undo a split if possible (undoing a level skip after we did a level is not saveable)
-// TODO: Handle the case where we skip a split
+/// Saves where the run is at, so that it can be resumed from there, along with the game time from the game loop.
+/// A finished route has nothing left to resume, so its progress is removed instead.
+fn store_progress(data: &GameData, split_index: usize, route: &Route, loop_time: &LoopTime) {
+    if split_index >= route.splits().len() {
+        Progress::clear();
+    } else if let Some(mut progress) = data.progress(split_index, route.splits().len()) {
+        progress.game_time = loop_time.time();
+        progress.store();
+    }
+}
+
+/// Returns true if LiveSplit is timing a run
+fn run_in_progress() -> bool {
+    !matches!(timer::state(), TimerState::NotRunning | TimerState::Unknown)
+}
+
+/// Carries on tracking from the progress saved at the last split, returning it if it was resumed.
+/// Only a run that LiveSplit is still timing can be resumed, not one about to be started or one already finished.
+fn resume_run(settings: &Settings, data: &mut GameData, route: &Route) -> Option<Progress> {
+    if !settings.resume_runs
+        || settings.il_mode
+        || !matches!(timer::state(), TimerState::Running | TimerState::Paused)
+    {
+        return None;
+    }
+    let progress = Progress::load().filter(|progress| progress.resumable(route.splits().len()))?;
+    data.resume(&progress).then_some(progress)
+}
+
+/// Shows the deaths over the run and on the current level in timer variables, if they are tracked
+fn show_deaths(data: &GameData) {
+    if !data.tracks_deaths() {
+        return;
+    }
+    let (deaths, level_deaths) = data.deaths();
+    let mut text = ArrayString::<16>::new();
+    let _ = write!(text, "{deaths}");
+    timer::set_variable("Deaths", &text);
+    text.clear();
+    let _ = write!(text, "{level_deaths}");
+    timer::set_variable("Level deaths", &text);
+}
+
+/// Pauses the run when the game is left or SC2 is closed, returning true if it was running. Only starting a new game
+/// resets the run.
+fn pause_for_left_game() -> bool {
+    if timer::state() != TimerState::Running {
+        return false;
+    }
+    log!("THE GAME WAS LEFT, PAUSING THE TIMER!");
+    timer::pause();
+    true
+}
@@ -0,0 +1,122 @@
+use core::fmt::Write;
+
+use asr::{arrayvec::ArrayString, timer};
+
+use crate::log;
+
+/// Timer variable the game state is shown in
+const GAME_STATE_VARIABLE: &str = "Game state";
+
+/// Where the game is at, as far as can be told from the exp struct.
+/// Nothing in it tells a game that was won or lost from one that is still going, so there is no `Defeat`: a lost game
+/// is just left, and `Victory` is inferred from the route rather than read from the game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+    /// Out of a game, while SC2 is scanned for the exp struct
+    Menu,
+    /// The exp struct is there, but the exp has not been loaded from the bank yet
+    Loading,
+    /// Playing the game
+    InGame,
+    /// The last split of the route was done, while the game is still open. This comes from the route, not from the
+    /// game, so it is also reached by finishing a route that stops short of the end of the game.
+    Victory,
+    /// The game was left, which is when its exp struct goes away, whether or not the route was finished
+    Left,
+    /// SC2 is not running
+    Closed,
+}
+
+impl GameState {
+    /// Returns true if a game is open, whether or not it is over
+    pub fn in_game(&self) -> bool {
+        matches!(
+            self,
+            GameState::Loading | GameState::InGame | GameState::Victory
+        )
+    }
+}
+
+/// A change of game state that the timer should follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifecycleEvent {
+    /// A game was started, either from the menu or by starting SC2
+    NewGame,
+    /// The game was left, or SC2 was closed
+    LeftGame,
+    /// The route was finished. As the game state it comes from is inferred, this is only ever logged, and never starts,
+    /// pauses or resets the timer.
+    Won,
+}
+
+/// State machine for the game lifecycle, so that starting, pausing and resetting the timer follow the game
+pub struct Lifecycle {
+    state: GameState,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            state: GameState::Closed,
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+    /// Moves on to the state that was seen, returning what the change means for the timer
+    pub fn update(&mut self, state: GameState) -> Option<LifecycleEvent> {
+        let old_state = core::mem::replace(&mut self.state, state);
+        if old_state == state {
+            return None;
+        }
+        log!("Game state changed from {old_state:?} to {state:?}");
+        let mut name = ArrayString::<16>::new();
+        let _ = write!(name, "{state:?}");
+        timer::set_variable(GAME_STATE_VARIABLE, &name);
+        match (old_state.in_game(), state.in_game()) {
+            (false, true) => Some(LifecycleEvent::NewGame),
+            (true, false) => Some(LifecycleEvent::LeftGame),
+            _ if state == GameState::Victory => Some(LifecycleEvent::Won),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_victory_out_of_starting_and_resetting() {
+        let mut lifecycle = Lifecycle::default();
+        assert_eq!(lifecycle.update(GameState::Menu), None);
+        assert_eq!(
+            lifecycle.update(GameState::Loading),
+            Some(LifecycleEvent::NewGame)
+        );
+        assert_eq!(lifecycle.update(GameState::InGame), None);
+        assert_eq!(
+            lifecycle.update(GameState::Victory),
+            Some(LifecycleEvent::Won)
+        );
+        // Undoing the last split by hand takes the game back out of victory without starting a new one
+        assert_eq!(lifecycle.update(GameState::InGame), None);
+        assert_eq!(
+            lifecycle.update(GameState::Victory),
+            Some(LifecycleEvent::Won)
+        );
+        // A won game is left like any other, and only the next game starts a new run
+        assert_eq!(
+            lifecycle.update(GameState::Left),
+            Some(LifecycleEvent::LeftGame)
+        );
+        assert_eq!(lifecycle.update(GameState::Menu), None);
+        assert_eq!(
+            lifecycle.update(GameState::Loading),
+            Some(LifecycleEvent::NewGame)
+        );
+    }
+}
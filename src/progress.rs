@@ -0,0 +1,169 @@
+use core::fmt::{self, Write};
+
+use asr::{arrayvec::ArrayString, settings, time::Duration};
+
+use crate::split_type::{Difficulty, SplitType};
+
+/// Settings map key holding the progress through the current run, written on every split
+const PROGRESS_KEY: &str = "progress";
+
+/// Where the run was at the last split, which is enough to carry on from there if SC2 or the splitter restarts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Absolute exp at the split, which the game has to be at again to resume
+    pub exp: i32,
+    pub level: SplitType,
+    pub pad: i32,
+    pub loop_num: i32,
+    pub difficulty: Option<Difficulty>,
+    /// Deaths over the run so far
+    pub deaths: i32,
+    /// Index of the next split in the route
+    pub split_index: usize,
+    /// Length of the route, so that the progress is not resumed with a different route
+    pub route_len: usize,
+    /// Game time told from the game loop, if it was, for game time to carry on from
+    pub game_time: Option<Duration>,
+}
+
+impl Progress {
+    /// Loads the progress from the settings map, if any was stored
+    pub fn load() -> Option<Self> {
+        let text = settings::Map::load()
+            .get(PROGRESS_KEY)?
+            .get_array_string::<128>()?
+            .ok()?;
+        Self::parse(&text)
+    }
+    /// Stores the progress in the settings map, replacing any progress stored before
+    pub fn store(&self) {
+        let mut text = ArrayString::<128>::new();
+        let _ = write!(text, "{self}");
+        Self::store_text(&text);
+    }
+    /// Returns true if the progress is through the route of that length and has splits left to resume with
+    pub fn resumable(&self, route_len: usize) -> bool {
+        self.route_len == route_len && self.split_index < route_len
+    }
+    /// Removes the stored progress, for when the run is over
+    pub fn clear() {
+        Self::store_text("");
+    }
+    fn store_text(text: &str) {
+        let map = settings::Map::load();
+        map.insert(PROGRESS_KEY, &text.into());
+        map.store();
+    }
+    /// Parses the progress in the form written by `Display`, such as
+    /// `exp=1234 level=5 pad=3 loop=1 difficulty=2 split=4/12 deaths=3 time=81250`, with the game time in
+    /// milliseconds. Deaths were not always stored, so they default to none, and the game time is only there if it
+    /// was told from the game loop.
+    fn parse(text: &str) -> Option<Self> {
+        let mut exp = None;
+        let mut level = None;
+        let mut pad = None;
+        let mut loop_num = None;
+        let mut difficulty = None;
+        let mut split = None;
+        let mut deaths = None;
+        let mut game_time = None;
+        for field in text.split_whitespace() {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "exp" => exp = Some(value.parse().ok()?),
+                "level" => level = Some(SplitType::from_raw_level(value.parse().ok()?)?),
+                "pad" => pad = Some(value.parse().ok()?),
+                "loop" => loop_num = Some(value.parse().ok()?),
+                // Zero is an unknown difficulty
+                "difficulty" => {
+                    difficulty = Some(match value.parse().ok()? {
+                        0 => None,
+                        multiplier => Some(Difficulty::from_multiplier(multiplier)?),
+                    })
+                }
+                "split" => {
+                    let (index, len) = value.split_once('/')?;
+                    split = Some((index.parse().ok()?, len.parse().ok()?));
+                }
+                "deaths" => deaths = Some(value.parse().ok()?),
+                "time" => game_time = Some(Duration::milliseconds(value.parse().ok()?)),
+                _ => return None,
+            }
+        }
+        let (split_index, route_len) = split?;
+        Some(Self {
+            exp: exp?,
+            level: level?,
+            pad: pad?,
+            loop_num: loop_num?,
+            difficulty: difficulty?,
+            deaths: deaths.unwrap_or_default(),
+            split_index,
+            route_len,
+            game_time,
+        })
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "exp={} level={} pad={} loop={} difficulty={} split={}/{} deaths={}",
+            self.exp,
+            self.level.raw_level().unwrap_or_default(),
+            self.pad,
+            self.loop_num,
+            self.difficulty.map_or(0, |difficulty| difficulty as i32),
+            self.split_index,
+            self.route_len,
+            self.deaths
+        )?;
+        match self.game_time {
+            Some(game_time) => write!(f, " time={}", game_time.whole_milliseconds()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "exp=1234 level=5 pad=3 loop=1 difficulty=2 split=4/12 deaths=3 time=81250";
+
+    #[test]
+    fn parses_what_it_writes() {
+        let progress = Progress::parse(TEXT).unwrap();
+        assert_eq!(progress.split_index, 4);
+        assert_eq!(progress.game_time, Some(Duration::milliseconds(81250)));
+        let mut text = ArrayString::<128>::new();
+        write!(text, "{progress}").unwrap();
+        assert_eq!(text.as_str(), TEXT);
+    }
+
+    #[test]
+    fn needs_every_field_but_deaths_and_time() {
+        let progress =
+            Progress::parse("exp=1234 level=5 pad=3 loop=1 difficulty=0 split=4/12").unwrap();
+        assert_eq!((progress.deaths, progress.game_time), (0, None));
+        // Each field only counts once, so repeating one does not make up for another that is missing
+        for text in [
+            "exp=1234 level=5 pad=3 loop=1 difficulty=2",
+            "exp=1234 exp=1234 level=5 pad=3 loop=1 split=4/12",
+            "exp=1234 level=5 pad=3 loop=1 difficulty=2 deaths=3 deaths=3",
+        ] {
+            assert_eq!(Progress::parse(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn only_resumes_an_unfinished_run_of_the_route() {
+        let progress = Progress::parse(TEXT).unwrap();
+        assert!(progress.resumable(12));
+        assert!(!progress.resumable(11));
+        let finished =
+            Progress::parse("exp=1234 level=5 pad=3 loop=1 difficulty=2 split=12/12").unwrap();
+        assert!(!finished.resumable(12));
+    }
+}
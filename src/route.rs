@@ -0,0 +1,491 @@
+use core::fmt::{self, Write};
+
+use asr::{
+    arrayvec::{ArrayString, ArrayVec},
+    settings::{self, Gui},
+    timer,
+};
+
+use crate::{
+    condition::Condition,
+    log,
+    split_state::MAX_SPLIT_STATES,
+    split_type::{parse_term, top_level_items, ParseSplitError, SplitType},
+};
+
+/// Largest number of splits a route can have
+pub const MAX_SPLITS: usize = 64;
+/// Largest number of custom conditions a route can have
+pub const MAX_CONDITIONS: usize = 16;
+/// Largest number of combinator groups a route can have
+pub const MAX_GROUPS: usize = 16;
+/// Largest number of members a combinator group can have
+pub const MAX_GROUP_SIZE: usize = 8;
+/// Settings map key holding the custom split conditions, separated by `;`
+const CUSTOM_SPLITS_KEY: &str = "custom_splits";
+/// Settings map key holding the route for the custom category, such as `Level1, Bot2000, PadsCrossed(5)`
+const ROUTE_KEY: &str = "route";
+/// Timer variable that any custom split parse error is reported in
+const CUSTOM_SPLITS_ERROR_VARIABLE: &str = "Custom split error";
+/// Timer variable that any route validation error is reported in
+const ROUTE_ERROR_VARIABLE: &str = "Route error";
+
+/// Splits for a full run that ends on the diablo kill
+const ANY_PERCENT: [SplitType; 12] = [
+    SplitType::Level1,
+    SplitType::Level2,
+    SplitType::Level3,
+    SplitType::Bot2000,
+    SplitType::Level4,
+    SplitType::Level5,
+    SplitType::Level6,
+    SplitType::Odin,
+    SplitType::Level7,
+    SplitType::Level8,
+    SplitType::Level9,
+    SplitType::Diablo,
+];
+
+/// Splits for a full run that continues past diablo and ends on the cow level completion
+const COW_LEVEL_PERCENT: [SplitType; 13] = [
+    SplitType::Level1,
+    SplitType::Level2,
+    SplitType::Level3,
+    SplitType::Bot2000,
+    SplitType::Level4,
+    SplitType::Level5,
+    SplitType::Level6,
+    SplitType::Odin,
+    SplitType::Level7,
+    SplitType::Level8,
+    SplitType::Level9,
+    SplitType::Diablo,
+    SplitType::CowLevel,
+];
+
+#[derive(Gui, Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    /// Any% (ends on Diablo)
+    #[default]
+    AnyPercent,
+    /// Cow Level% (ends on the Cow Level)
+    CowLevelPercent,
+    /// Custom (the route setting, or one split per condition in the custom_splits setting)
+    Custom,
+}
+
+impl Category {
+    /// Returns the preset splits for this category, in the order they are expected to happen
+    pub fn splits(&self) -> &'static [SplitType] {
+        match self {
+            Category::AnyPercent => &ANY_PERCENT,
+            Category::CowLevelPercent => &COW_LEVEL_PERCENT,
+            Category::Custom => &[],
+        }
+    }
+}
+
+/// A split in the route that can never happen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteError {
+    pub index: usize,
+    pub split: SplitType,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "split {} ({:?}): {}",
+            self.index + 1,
+            self.split,
+            self.reason
+        )
+    }
+}
+
+/// A split in the route text that could not be parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteParseError {
+    pub index: usize,
+    pub error: ParseSplitError,
+}
+
+impl fmt::Display for RouteParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "split {}: {}", self.index + 1, self.error)
+    }
+}
+
+/// Checks that the raw level is a level, and has pads if needed
+fn validate_level(raw_level: i32, needs_pads: bool) -> Result<(), &'static str> {
+    match SplitType::from_raw_level(raw_level) {
+        None => Err("there is no level with this raw level"),
+        Some(level) if needs_pads && level.pad_count().is_none() => {
+            Err("this level has no pads to cross")
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// The splits to run through, along with any custom conditions and combinator groups they refer to
+pub struct Route {
+    splits: ArrayVec<SplitType, MAX_SPLITS>,
+    conditions: ArrayVec<Condition, MAX_CONDITIONS>,
+    groups: ArrayVec<ArrayVec<SplitType, MAX_GROUP_SIZE>, MAX_GROUPS>,
+}
+
+impl Route {
+    /// Loads the route for the category, parsing the custom conditions from the settings map as needed
+    pub fn load(category: Category) -> Self {
+        let mut route = Self {
+            splits: category.splits().iter().copied().collect(),
+            conditions: ArrayVec::new(),
+            groups: ArrayVec::new(),
+        };
+        let mut error = ArrayString::<256>::new();
+        if category == Category::Custom {
+            route.load_conditions();
+            match settings::Map::load()
+                .get(ROUTE_KEY)
+                .and_then(|value| value.get_array_string::<1024>())
+            {
+                Some(Ok(text)) => {
+                    if let Err(e) = route.parse_splits(&text) {
+                        let _ = write!(error, "{e}");
+                    }
+                }
+                // Without a route, split on each custom condition in order
+                _ => {
+                    for index in 0..route.conditions.len() {
+                        route.push_split(SplitType::Custom {
+                            index: index as i32,
+                        });
+                    }
+                }
+            }
+        }
+        // Reject routes that can never be finished instead of waiting on them forever
+        if error.is_empty() {
+            if let Err(e) = route.validate() {
+                let _ = write!(error, "{e}");
+            }
+        }
+        if error.is_empty() {
+            log!("Loaded route: {route}");
+        } else {
+            log!("Rejecting the route because of: {error}");
+            route.splits.clear();
+        }
+        timer::set_variable(ROUTE_ERROR_VARIABLE, &error);
+        route
+    }
+    /// Parses the splits of a route, such as `Level1, AllOf(Odin, EnergyCubes(3)), Diablo`, adding them to the end.
+    /// Combinators may list their members, which are added as new groups, or refer to an existing group by index.
+    pub fn parse_splits(&mut self, text: &str) -> Result<(), RouteParseError> {
+        for (index, item) in top_level_items(text).enumerate() {
+            let error = |error| RouteParseError { index, error };
+            let split = self.parse_split(item).map_err(error)?;
+            if !self.push_split(split) {
+                return Err(error(ParseSplitError::TooManySplits));
+            }
+        }
+        Ok(())
+    }
+    fn parse_split(&mut self, text: &str) -> Result<SplitType, ParseSplitError> {
+        let (name, arguments) = parse_term(text)?;
+        let combinator = |group| match name {
+            "AllOf" => Some(SplitType::AllOf { group }),
+            "AnyOf" => Some(SplitType::AnyOf { group }),
+            "Sequence" => Some(SplitType::Sequence { group }),
+            _ => None,
+        };
+        match arguments {
+            // A single number is a group index, which the split type parses on its own
+            Some(arguments)
+                if combinator(0).is_some() && arguments.trim().parse::<i32>().is_err() =>
+            {
+                let mut members = ArrayVec::<SplitType, MAX_GROUP_SIZE>::new();
+                for member in top_level_items(arguments) {
+                    let member = self.parse_split(member)?;
+                    members
+                        .try_push(member)
+                        .map_err(|_| ParseSplitError::TooManySplits)?;
+                }
+                let group = self
+                    .add_group(&members)
+                    .ok_or(ParseSplitError::TooManySplits)?;
+                combinator(group).ok_or(ParseSplitError::UnknownSplit)
+            }
+            _ => text.parse(),
+        }
+    }
+    /// Writes the split, writing out the members of combinators instead of their group index
+    fn write_split(&self, f: &mut fmt::Formatter<'_>, split: SplitType) -> fmt::Result {
+        let members = self.members(split);
+        if members.is_empty() {
+            return write!(f, "{split}");
+        }
+        let name = match split {
+            SplitType::AllOf { .. } => "AllOf",
+            SplitType::AnyOf { .. } => "AnyOf",
+            _ => "Sequence",
+        };
+        write!(f, "{name}(")?;
+        for (i, member) in members.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            self.write_split(f, *member)?;
+        }
+        f.write_str(")")
+    }
+    /// Checks that every split in the route, including the members of combinators, can happen
+    pub fn validate(&self) -> Result<(), RouteError> {
+        for (index, split) in self.splits.iter().enumerate() {
+            self.validate_split(*split).map_err(|reason| RouteError {
+                index,
+                split: *split,
+                reason,
+            })?;
+        }
+        Ok(())
+    }
+    fn validate_split(&self, split: SplitType) -> Result<(), &'static str> {
+        match split {
+            SplitType::PadCrossedForLevel { raw_level } => validate_level(raw_level, true),
+            SplitType::CompleteForLevel { raw_level } => validate_level(raw_level, false),
+            SplitType::PadCrossedForLevelOnLoop {
+                raw_level,
+                loop_num,
+            }
+            | SplitType::CompleteForLevelOnLoop {
+                raw_level,
+                loop_num,
+            } => {
+                let needs_pads = matches!(split, SplitType::PadCrossedForLevelOnLoop { .. });
+                validate_level(raw_level, needs_pads)?;
+                if loop_num < 1 {
+                    return Err("loops start at 1");
+                }
+                Ok(())
+            }
+            SplitType::PadsCrossed { num }
+            | SplitType::EnergyCubes { num }
+            | SplitType::Events { num, .. } => {
+                if num < 1 {
+                    return Err("the count must be at least 1");
+                }
+                Ok(())
+            }
+            SplitType::Custom { index } => self
+                .condition(index)
+                .map(|_| ())
+                .ok_or("there is no custom condition with this index"),
+            SplitType::AllOf { group }
+            | SplitType::AnyOf { group }
+            | SplitType::Sequence { group } => {
+                let members = usize::try_from(group)
+                    .ok()
+                    .and_then(|group| self.groups.get(group))
+                    .ok_or("there is no combinator group with this index")?;
+                if members.is_empty() {
+                    return Err("the combinator group has no members");
+                }
+                members
+                    .iter()
+                    .try_for_each(|member| self.validate_split(*member))
+            }
+            _ => Ok(()),
+        }
+    }
+    pub fn splits(&self) -> &[SplitType] {
+        &self.splits
+    }
+    /// Returns the custom condition for the index, if present
+    pub fn condition(&self, index: i32) -> Option<&Condition> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.conditions.get(index))
+    }
+    /// Returns the members of a combinator split, or nothing for any other split
+    pub fn members(&self, split: SplitType) -> &[SplitType] {
+        split
+            .group()
+            .and_then(|group| usize::try_from(group).ok())
+            .and_then(|group| self.groups.get(group))
+            .map_or(&[], |members| members.as_slice())
+    }
+    /// Returns the number of split states the split needs, which is one for itself plus those of all of its members
+    pub fn state_count(&self, split: SplitType) -> usize {
+        1 + self
+            .members(split)
+            .iter()
+            .map(|member| self.state_count(*member))
+            .sum::<usize>()
+    }
+    /// Adds a group of members for a combinator to refer to, returning the group index.
+    /// Members may only refer to groups that were added before, so combinators can never contain themselves.
+    pub fn add_group(&mut self, members: &[SplitType]) -> Option<i32> {
+        let index = self.groups.len() as i32;
+        if members
+            .iter()
+            .any(|member| member.group().is_some_and(|group| group >= index))
+        {
+            log!("Combinator group {index} can only contain groups before it!");
+            return None;
+        }
+        let group: ArrayVec<SplitType, MAX_GROUP_SIZE> =
+            members.iter().copied().take(MAX_GROUP_SIZE).collect();
+        if group.len() != members.len() {
+            log!("Combinator group {index} has more than {MAX_GROUP_SIZE} members!");
+            return None;
+        }
+        self.groups.try_push(group).ok()?;
+        // Any combinator over this group needs one state for itself on top of the members
+        if self.state_count(SplitType::AllOf { group: index }) > MAX_SPLIT_STATES {
+            log!("Combinator group {index} needs more than {MAX_SPLIT_STATES} split states!");
+            self.groups.pop();
+            return None;
+        }
+        Some(index)
+    }
+    /// Adds a split to the end of the route, returning false if the route is full
+    pub fn push_split(&mut self, split: SplitType) -> bool {
+        self.splits.try_push(split).is_ok()
+    }
+    /// Parses each condition in the settings map, for custom splits to refer to by index.
+    /// If any condition fails to parse, no conditions are added and the error is reported.
+    fn load_conditions(&mut self) {
+        let text = match settings::Map::load()
+            .get(CUSTOM_SPLITS_KEY)
+            .and_then(|value| value.get_array_string::<1024>())
+        {
+            Some(Ok(text)) => text,
+            _ => {
+                log!("No custom splits found in the settings under: {CUSTOM_SPLITS_KEY}");
+                return;
+            }
+        };
+        let mut error = ArrayString::<256>::new();
+        for (index, source) in text
+            .split(';')
+            .map(str::trim)
+            .filter(|source| !source.is_empty())
+            .enumerate()
+        {
+            match Condition::parse(source) {
+                Ok(condition) => {
+                    if self.conditions.try_push(condition).is_err() {
+                        let _ = write!(
+                            error,
+                            "too many custom splits, at most {MAX_CONDITIONS} are supported"
+                        );
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = write!(error, "custom split {} ({source}): {e}", index + 1);
+                    break;
+                }
+            }
+        }
+        if !error.is_empty() {
+            log!("Failed to load custom splits: {error}");
+            self.conditions.clear();
+        }
+        timer::set_variable(CUSTOM_SPLITS_ERROR_VARIABLE, &error);
+    }
+}
+
+impl fmt::Display for Route {
+    /// Writes the route in the same form that `parse_splits` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, split) in self.splits.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            self.write_split(f, *split)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_state::EventKind;
+
+    fn empty() -> Route {
+        Route {
+            splits: ArrayVec::new(),
+            conditions: ArrayVec::new(),
+            groups: ArrayVec::new(),
+        }
+    }
+
+    /// Parses the splits into a new route and writes it back out
+    fn round_trip(text: &str) -> (Route, ArrayString<1024>) {
+        let mut route = empty();
+        route.parse_splits(text).unwrap();
+        let mut written = ArrayString::new();
+        write!(written, "{route}").unwrap();
+        (route, written)
+    }
+
+    #[test]
+    fn writes_out_the_members_of_combinators() {
+        let text = "Level1, AllOf(Odin, EnergyCubes(3)), \
+            AnyOf(Sequence(Level2, PadsCrossed(5)), Events(Boss, 2)), Sequence(0), CompleteForLevelOnLoop(13, 1)";
+        let (route, written) = round_trip(text);
+        assert_eq!(
+            route.splits(),
+            [
+                SplitType::Level1,
+                SplitType::AllOf { group: 0 },
+                SplitType::AnyOf { group: 2 },
+                SplitType::Sequence { group: 0 },
+                SplitType::CompleteForLevelOnLoop {
+                    raw_level: 13,
+                    loop_num: 1
+                },
+            ]
+        );
+        assert_eq!(
+            route.members(SplitType::AnyOf { group: 2 }),
+            [
+                SplitType::Sequence { group: 1 },
+                SplitType::Events {
+                    event: EventKind::Boss,
+                    num: 2
+                },
+            ]
+        );
+        // Referring to a group by index is written out as its members
+        assert_eq!(
+            written.as_str(),
+            "Level1, AllOf(Odin, EnergyCubes(3)), \
+            AnyOf(Sequence(Level2, PadsCrossed(5)), Events(Boss, 2)), \
+            Sequence(Odin, EnergyCubes(3)), CompleteForLevelOnLoop(13, 1)"
+        );
+        // What is written reads back as the same route
+        let (reread, rewritten) = round_trip(&written);
+        assert_eq!(rewritten, written);
+        for (split, reread_split) in route.splits().iter().zip(reread.splits()) {
+            assert_eq!(route.state_count(*split), reread.state_count(*reread_split));
+        }
+    }
+
+    #[test]
+    fn reports_which_split_failed_to_parse() {
+        let mut route = empty();
+        assert_eq!(
+            route.parse_splits("Level1, AnyOf(Odin, Level10)"),
+            Err(RouteParseError {
+                index: 1,
+                error: ParseSplitError::UnknownSplit,
+            })
+        );
+    }
+}
@@ -17,7 +17,7 @@
 // 651468800
 // 455028736
 
-use core::mem::{self, MaybeUninit};
+use core::mem;
 
 use asr::{future::next_tick, Address, MemoryRangeFlags, Process};
 
@@ -51,83 +51,146 @@ const YIELD_FREQ: i32 = 100;
 // });
 // static EXP_PATTERN: u64x64 = simd::Simd::from_array(EXP_PATTERN_BYTES);
 
+/// Returns true if the exp signature is still right after the exp, which it is for as long as the game is open
+pub fn has_exp_signature(process: &Process, exp_pointer: Address) -> bool {
+    process
+        .read::<u128>(exp_pointer + 4)
+        .is_ok_and(|signature| signature == EXP_PATTERN_SIGNATURE)
+}
+
+/// Size of a page, the smallest amount that is safe to read
+const PAGE_SIZE: usize = 4 << 10;
+
+/// Addresses the exp can be in, under Windows and under Wine alike. Wine hands out Windows allocations in the same
+/// part of the address space as Windows does, while Linux maps Wine itself, its libraries and the stack far above it,
+/// from around 0x7F0000000000.
+const WINDOW_START: u64 = 0x00010000000;
+const WINDOW_END: u64 = WINDOW_START + 0x80000000000;
+
+/// Largest range scanned under Wine, where anything not mapped from a file is scanned otherwise, including large
+/// mappings such as graphics buffers that can take seconds each. Where SC2 keeps the exp under Wine has never been
+/// recorded, so this is a guess rather than a measured bound.
+pub const WINE_MAX_RANGE_SIZE: u64 = 256 << 20;
+
+/// How the memory of SC2 is laid out, which decides the ranges worth scanning for the exp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanLayout {
+    /// SC2 on Windows, where the heap is allocated in 64 KiB chunks in the latter half of the ranges
+    Windows,
+    /// SC2 under Wine or Proton, where committed memory shows up in page sized ranges and the Linux side of the
+    /// process (Wine itself and its libraries) is mapped alongside it
+    Wine,
+}
+
+/// Names of the executables that run Windows programs under Wine or Proton, whichever name the game then shows
+const WINE_EXECUTABLES: [&str; 4] = ["wine64-preloader", "wine-preloader", "wine64", "wine"];
+
+impl ScanLayout {
+    /// Under Wine the process is one of Wine's executables running the game, even when it is named after the game.
+    /// The path alone does not tell, since the runtime gives Linux style paths for Windows processes too.
+    pub fn detect(process: &Process) -> Self {
+        match process.get_path() {
+            Ok(path) => Self::from_executable(&path),
+            _ => ScanLayout::Windows,
+        }
+    }
+    /// Returns the layout for the path of the executable a process runs
+    pub fn from_executable(path: &str) -> Self {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        match WINE_EXECUTABLES.contains(&file_name) {
+            true => ScanLayout::Wine,
+            false => ScanLayout::Windows,
+        }
+    }
+    /// Returns true if the range, at the index in the memory ranges of the process, could hold the exp
+    pub fn should_scan(
+        &self,
+        index: usize,
+        base: Address,
+        size: u64,
+        flags: MemoryRangeFlags,
+    ) -> bool {
+        match self {
+            // We just willy-nilly assume that our address is in the latter half of pages, so skip the first 1000
+            // and check that the size is a multiple of the 64 KiB allocation size
+            ScanLayout::Windows => {
+                if index < 1000 || !size.is_multiple_of(64 << 10) {
+                    return false;
+                }
+            }
+            // The heap is never mapped from a file, unlike Wine's libraries and the Windows modules
+            ScanLayout::Wine => {
+                if !size.is_multiple_of(PAGE_SIZE as u64)
+                    || size > WINE_MAX_RANGE_SIZE
+                    || flags.contains(MemoryRangeFlags::PATH)
+                {
+                    return false;
+                }
+            }
+        }
+        // Skip pages that are not important since they have no read/write perms, or are out of bounds
+        flags.contains(MemoryRangeFlags::READ | MemoryRangeFlags::WRITE)
+            && !flags.contains(MemoryRangeFlags::EXECUTE)
+            && base.value() + size > WINDOW_START
+            && base.value() <= WINDOW_END
+    }
+}
+
 pub async fn find_exp_pattern(process: &Process) -> Option<Address> {
-    let mut addr = Address::new(0x00010000000);
-    //                               0x260C8C5D77C
-    let overall_end = addr.value() + 0x80000000000;
+    let layout = ScanLayout::detect(process);
     // Array size is 64KB
-    let mut buf = [MaybeUninit::uninit(); (64 << 10)];
-    let mut i = 0;
+    let mut buf = [0; (64 << 10)];
     let mut read_count = 0;
-    for range in process.memory_ranges() {
+    for (i, range) in process.memory_ranges().enumerate() {
         // First, get the start and size of the page to see if we should look at it
-        if let Ok((chunk_base, chunk_size)) = range.range() {
-            i += 1;
-            // Skip all pages where i is less than some fixed value
-            // In this case, we just willy-nilly assume that our address is in the latter half of pages
-            // So, skip the first 3000
-            if i <= 1000 {
-                continue;
-            }
-            // Check that chunk_size is a multiple of buf.size()
-            if (chunk_size as usize % buf.len()) != 0 {
-                continue;
-            }
-            // Check the address range against our addr and overall_end
-            if chunk_base + chunk_size <= addr || chunk_base.value() > overall_end {
-                // This page is out of bounds, ignore it
-                continue;
-            }
-            // Page is in-bounds, check the flags
-            if let Ok(flags) = range.flags() {
-                if !flags.contains(MemoryRangeFlags::READ | MemoryRangeFlags::WRITE)
-                    || flags.contains(MemoryRangeFlags::EXECUTE)
-                {
-                    // Skip pages that are not important since they have no read/write perms
-                    continue;
-                }
+        let Ok((chunk_base, chunk_size)) = range.range() else {
+            continue;
+        };
+        // Skip pages we can't read flags for
+        let Ok(flags) = range.flags() else {
+            continue;
+        };
+        if !layout.should_scan(i, chunk_base, chunk_size, flags) {
+            continue;
+        }
+        // At this point, read the page into our buffer repeatedly until we have gone through all the size
+        let chunk_end = chunk_base.value() + chunk_size;
+        let mut addr = chunk_base;
+        while addr.value() < chunk_end {
+            // We read the whole buffer at once where it fits, as the syscall overhead is quite high,
+            // and otherwise a single 4 KiB page, which is safe to read either fully or not at all.
+            let read_len = if chunk_end - addr.value() >= buf.len() as u64 {
+                buf.len()
             } else {
-                // Skip pages we can't read flags for
-                continue;
-            }
-            // At this point, read the page into our buffer repeatedly until we have gone through all the size or we have reached the end of our size request
-            let chunk_end = chunk_base.value() + chunk_size;
-            // We go sequentially in pages, so if we skip a range, capture that.
-            addr = addr.value().max(chunk_base.value()).into();
-            while addr.value() < chunk_end {
-                // We round up to the 4 KiB address boundary as that's a single
-                // page, which is safe to read either fully or not at all. We do
-                // this to do a single read rather than many small ones as the
-                // syscall overhead is a quite high.
-
-                // TODO: We know that our end scan addr will always be 4KiB page aligned
-                if unsafe {
-                    process_read(
-                        mem::transmute_copy(process),
-                        addr,
-                        buf.as_mut_ptr().cast(),
-                        buf.len(),
-                    )
-                } {
-                    let read_data = unsafe { MaybeUninit::array_assume_init(buf) };
-                    if let Some(offset) = compare_equivalence(&read_data) {
-                        // Exp offset is -4 off the pattern
-                        let result = addr.add(offset as u64).add_signed(-4);
-                        log!("Found exp at: {result:?}");
-                        return Some(addr.add(offset as u64).add_signed(-4));
-                    }
+                PAGE_SIZE
+            };
+            if unsafe {
+                process_read(
+                    mem::transmute_copy(process),
+                    addr,
+                    buf.as_mut_ptr(),
+                    read_len,
+                )
+            } {
+                let offset = if read_len == buf.len() {
+                    compare_equivalence(&buf)
+                } else {
+                    compare_equivalence::<PAGE_SIZE>(buf[..PAGE_SIZE].try_into().unwrap())
+                };
+                if let Some(offset) = offset {
+                    // Exp offset is -4 off the pattern
+                    let result = addr.add(offset as u64).add_signed(-4);
+                    log!("Found exp at: {result:?} with the {layout:?} layout");
+                    return Some(result);
                 }
-                // Move the address forward, eventually we will be at chunk_end, which will be the next chunk for us to read
-                addr = addr.add(buf.len() as u64);
-                // TODO: Yield here
-            }
-            // Yield back after READING pages, since that's the actually slow part
-            read_count += 1;
-            if read_count % YIELD_FREQ == 0 {
-                // let page = i - 1;
-                // log!("Yielding at: {page} page, with a read count of: {read_count}");
-                next_tick().await;
             }
+            // Move the address forward, eventually we will be at chunk_end, which will be the next chunk for us to read
+            addr = addr.add(read_len as u64);
+        }
+        // Yield back after READING pages, since that's the actually slow part
+        read_count += 1;
+        if read_count % YIELD_FREQ == 0 {
+            next_tick().await;
         }
     }
     log!(
@@ -1,44 +1,97 @@
-use crate::split_type::SplitType;
+use asr::arrayvec::ArrayVec;
 
+use crate::{route::Route, split_type::SplitType};
+
+/// Largest number of states a single split can need, counting every member of its combinators
+pub const MAX_SPLIT_STATES: usize = 32;
+
+/// States for a split, laid out with each combinator followed by the states of its members in order
+pub type SplitStates = ArrayVec<SplitState, MAX_SPLIT_STATES>;
+
+/// Kinds of events that are counted while waiting on a split
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Pad,
+    Cube,
+    Boss,
+    Level,
+    /// Counts the amount of exp gained rather than the number of gains
+    ExpGained,
+    DifficultyChange,
+}
+
+impl EventKind {
+    pub const COUNT: usize = 6;
+    pub const ALL: [EventKind; Self::COUNT] = [
+        EventKind::Pad,
+        EventKind::Cube,
+        EventKind::Boss,
+        EventKind::Level,
+        EventKind::ExpGained,
+        EventKind::DifficultyChange,
+    ];
+    /// Returns the name of the event as it is written in routes
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Pad => "Pad",
+            EventKind::Cube => "Cube",
+            EventKind::Boss => "Boss",
+            EventKind::Level => "Level",
+            EventKind::ExpGained => "ExpGained",
+            EventKind::DifficultyChange => "DifficultyChange",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Counts of each kind of event since the split started
+#[derive(Default)]
 pub struct SplitState {
-    pads_remaining: i32,
-    cubes_remaining: i32,
+    counts: [i32; EventKind::COUNT],
+    // Combinator state, a bit per member that has split and how far along a sequence we are
+    members_split: u32,
+    progress: usize,
 }
 
 impl SplitState {
-    pub fn visit_pad(&mut self) {
-        self.pads_remaining -= 1;
-    }
-    pub fn place_cube(&mut self) {
-        self.cubes_remaining -= 1;
-    }
-    pub fn pads(&self) -> i32 {
-        self.pads_remaining
-    }
-    pub fn cubes(&self) -> i32 {
-        self.cubes_remaining
-    }
-    pub fn from_split(full_split: Option<&SplitType>) -> Self {
-        if let Some(split) = full_split {
-            match split {
-                SplitType::PadsCrossed { num } => SplitState {
-                    cubes_remaining: 0,
-                    pads_remaining: *num,
-                },
-                SplitType::EnergyCubes { num } => SplitState {
-                    cubes_remaining: *num,
-                    pads_remaining: 0,
-                },
-                _ => SplitState {
-                    pads_remaining: 0,
-                    cubes_remaining: 0,
-                },
-            }
-        } else {
-            SplitState {
-                pads_remaining: 0,
-                cubes_remaining: 0,
-            }
+    /// Adds to the count of an event
+    pub fn record(&mut self, kind: EventKind, amount: i32) {
+        self.counts[kind as usize] += amount;
+    }
+    /// Returns how many times the event has happened since the split started
+    pub fn count(&self, kind: EventKind) -> i32 {
+        self.counts[kind as usize]
+    }
+    /// Marks the members in the bitmask as having split
+    pub fn split_members(&mut self, members: u32) {
+        self.members_split |= members;
+    }
+    /// Moves a sequence on to its next member
+    pub fn advance(&mut self) {
+        self.progress += 1;
+    }
+    pub fn members_split(&self) -> u32 {
+        self.members_split
+    }
+    pub fn progress(&self) -> usize {
+        self.progress
+    }
+    fn push_states(states: &mut SplitStates, split: &SplitType, route: &Route) {
+        if states.try_push(Default::default()).is_err() {
+            return;
+        }
+        for member in route.members(*split) {
+            Self::push_states(states, member, route);
+        }
+    }
+    pub fn from_split(full_split: Option<&SplitType>, route: &Route) -> SplitStates {
+        let mut states = ArrayVec::new();
+        match full_split {
+            Some(split) => Self::push_states(&mut states, split, route),
+            None => states.push(Default::default()),
         }
+        states
     }
 }
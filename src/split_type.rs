@@ -1,3 +1,7 @@
+use core::{fmt, str::FromStr};
+
+use crate::split_state::EventKind;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SplitType {
     Manual,
@@ -22,11 +26,23 @@ pub enum SplitType {
     OdinCube,
     PadCrossed,
     // Discriminated values here represent options for the split type
+    // The level splits fire on every loop through the levels, the loop variants only on the given loop (starting at 1)
     PadCrossedForLevel { raw_level: i32 },
     CompleteForLevel { raw_level: i32 },
+    PadCrossedForLevelOnLoop { raw_level: i32, loop_num: i32 },
+    CompleteForLevelOnLoop { raw_level: i32, loop_num: i32 },
     PadsCrossed { num: i32 },
     EnergyCubes { num: i32 },
     // TODO: Add splits for energy feeding for b2k, odin (+ healing), diablo chaser hit 1 and 3
+    // Splits once the event has happened num times since the previous split (for exp gained, once num exp was gained)
+    Events { event: EventKind, num: i32 },
+    // Index into the custom conditions of the route
+    Custom { index: i32 },
+    // Combinators over a group of splits in the route
+    // AllOf splits once every member has split, AnyOf when any member splits and Sequence once every member has split in order
+    AllOf { group: i32 },
+    AnyOf { group: i32 },
+    Sequence { group: i32 },
     // TODO: Once we move away from JUST exp, add splits for b2k rocks, odin flood phases, diablo p1/p2 on insane, etc.
 }
 
@@ -38,13 +54,27 @@ pub enum Difficulty {
     Insane = 3,
 }
 
+impl Difficulty {
+    /// Returns the difficulty with the multiplier, or None if there is no such difficulty
+    pub fn from_multiplier(multiplier: i32) -> Option<Self> {
+        match multiplier {
+            1 => Some(Difficulty::Normal),
+            2 => Some(Difficulty::Hard),
+            3 => Some(Difficulty::Insane),
+            _ => None,
+        }
+    }
+}
+
 // Largest EXP difference is diablo on insane win for a total of 900 exp in one tick
 pub const LARGEST_EXP_DIFFERENCE: i32 = 300 * 3;
+/// Number of pads in a standard level
+pub const PAD_COUNT: i32 = 19;
 
 impl SplitType {
-    /// Returns the raw numerical level
-    pub fn raw_level(&self) -> i32 {
-        match self {
+    /// Returns the raw numerical level, or None if this is not a level
+    pub fn raw_level(&self) -> Option<i32> {
+        Some(match self {
             Self::Level1 => 1,
             Self::Level2 => 2,
             Self::Level3 => 3,
@@ -58,11 +88,12 @@ impl SplitType {
             Self::Level9 => 11,
             Self::Diablo => 12,
             Self::CowLevel => 13,
-            _ => panic!("Cannot get the raw level of: {self:?}"),
-        }
+            _ => return None,
+        })
     }
-    pub fn from_raw_level(raw_level: i32) -> Self {
-        match raw_level {
+    /// Returns the level for the raw numerical level, or None if there is no such level
+    pub fn from_raw_level(raw_level: i32) -> Option<Self> {
+        Some(match raw_level {
             1 => Self::Level1,
             2 => Self::Level2,
             3 => Self::Level3,
@@ -76,15 +107,24 @@ impl SplitType {
             11 => Self::Level9,
             12 => Self::Diablo,
             13 => Self::CowLevel,
-            _ => panic!("Cannot get the level for raw level of: {raw_level}"),
+            _ => return None,
+        })
+    }
+    /// Returns the exp for a cube on a particular boss level, or None for any other level
+    pub fn cube_exp(&self, difficulty: Difficulty) -> Option<i32> {
+        match self {
+            Self::Bot2000 => Some(6 * difficulty as i32),
+            Self::Odin => Some(10 * difficulty as i32),
+            _ => None,
         }
     }
-    /// Returns the exp for a cube on a particular boss level
-    pub fn cube_exp(&self, difficulty: Difficulty) -> i32 {
+    /// Returns the index of the group of members for a combinator, or None otherwise
+    pub fn group(&self) -> Option<i32> {
         match self {
-            Self::Bot2000 => 6 * difficulty as i32,
-            Self::Odin => 10 * difficulty as i32,
-            _ => panic!("Cannot call cube_exp on: {self:?} for difficulty: {difficulty:?}"),
+            SplitType::AllOf { group }
+            | SplitType::AnyOf { group }
+            | SplitType::Sequence { group } => Some(*group),
+            _ => None,
         }
     }
     /// Returns true if we need the difficulty to be computed for the current split type
@@ -111,6 +151,10 @@ impl SplitType {
             SplitType::Bot2000 | SplitType::Odin | SplitType::Diablo | SplitType::CowLevel
         )
     }
+    /// Returns the number of pads that need to be crossed to complete this level, or None if it is not completed by pads
+    pub fn pad_count(&self) -> Option<i32> {
+        self.is_normal_level().then_some(PAD_COUNT)
+    }
     /// Returns the amount of exp granted for a single pad, or None otherwise
     pub fn per_pad_exp(&self, difficulty: Difficulty) -> Option<i32> {
         match self {
@@ -135,7 +179,9 @@ impl SplitType {
             | SplitType::Level5
             | SplitType::Level6
             | SplitType::Level7
-            | SplitType::Level8 => Some((1 + self.raw_level()) * difficulty as i32),
+            | SplitType::Level8 => self
+                .raw_level()
+                .map(|raw_level| (1 + raw_level) * difficulty as i32),
             SplitType::Manual
             | SplitType::ExpGained
             | SplitType::BossComplete
@@ -146,20 +192,28 @@ impl SplitType {
             | SplitType::PadCrossed => None,
             SplitType::PadCrossedForLevel { .. }
             | SplitType::CompleteForLevel { .. }
+            | SplitType::PadCrossedForLevelOnLoop { .. }
+            | SplitType::CompleteForLevelOnLoop { .. }
             | SplitType::PadsCrossed { .. }
-            | SplitType::EnergyCubes { .. } => None,
+            | SplitType::EnergyCubes { .. }
+            | SplitType::Events { .. }
+            | SplitType::Custom { .. }
+            | SplitType::AllOf { .. }
+            | SplitType::AnyOf { .. }
+            | SplitType::Sequence { .. } => None,
         }
     }
-    /// Returns the boss completion exp, panicking if the current level type is not a boss level
-    pub fn boss_exp(&self, difficulty: Difficulty) -> i32 {
+    /// Returns the boss completion exp, or None if the current level type is not a boss level
+    pub fn boss_exp(&self, difficulty: Difficulty) -> Option<i32> {
         match self {
             SplitType::Bot2000 | SplitType::Odin | SplitType::Diablo | SplitType::CowLevel => {
-                self.per_pad_exp(difficulty).expect("unreachable")
+                self.per_pad_exp(difficulty)
             }
-            _ => panic!("boss_exp cannot be called with: {self:?} for difficulty: {difficulty:?}"),
+            _ => None,
         }
     }
-    pub fn standard_exp(&self, difficulty: Difficulty) -> i32 {
+    /// Returns the exp for a pad on a standard level, or None if the current level type is not a standard level
+    pub fn standard_exp(&self, difficulty: Difficulty) -> Option<i32> {
         match self {
             SplitType::Level1
             | SplitType::Level2
@@ -169,15 +223,13 @@ impl SplitType {
             | SplitType::Level6
             | SplitType::Level7
             | SplitType::Level8
-            | SplitType::Level9 => self.per_pad_exp(difficulty).expect("unreachable"),
-            _ => panic!(
-                "standard_exp cannot be called with: {self:?} for difficulty: {difficulty:?}"
-            ),
+            | SplitType::Level9 => self.per_pad_exp(difficulty),
+            _ => None,
         }
     }
-    /// Modifies the current to be the next raw level, if there is one that exists
-    pub fn next(&self) -> SplitType {
-        match self {
+    /// Returns the next raw level, or None if this is not a level
+    pub fn next(&self) -> Option<SplitType> {
+        Some(match self {
             SplitType::Level1 => SplitType::Level2,
             SplitType::Level2 => SplitType::Level3,
             SplitType::Level3 => SplitType::Bot2000,
@@ -192,14 +244,392 @@ impl SplitType {
             SplitType::Diablo => SplitType::CowLevel,
             // Loop back around for cowlevel
             SplitType::CowLevel => SplitType::Level1,
-            _ => panic!("Cannot call next on a non-level split-type: {self:?}"),
-        }
+            _ => return None,
+        })
     }
 }
 /// Returns true if any boss level was just completed for this difficulty
 pub fn any_boss(difference: i32, difficulty: Difficulty) -> bool {
-    SplitType::Bot2000.boss_exp(difficulty) == difference
-        || SplitType::Odin.boss_exp(difficulty) == difference
-        || SplitType::Diablo.boss_exp(difficulty) == difference
-        || SplitType::CowLevel.boss_exp(difficulty) == difference
+    SplitType::Bot2000.boss_exp(difficulty) == Some(difference)
+        || SplitType::Odin.boss_exp(difficulty) == Some(difference)
+        || SplitType::Diablo.boss_exp(difficulty) == Some(difference)
+        || SplitType::CowLevel.boss_exp(difficulty) == Some(difference)
+}
+
+/// Split types without any options, along with how they are written in routes
+const UNIT_SPLITS: [(&str, SplitType); 21] = [
+    ("Manual", SplitType::Manual),
+    ("Level1", SplitType::Level1),
+    ("Level2", SplitType::Level2),
+    ("Level3", SplitType::Level3),
+    ("Bot2000", SplitType::Bot2000),
+    ("Level4", SplitType::Level4),
+    ("Level5", SplitType::Level5),
+    ("Level6", SplitType::Level6),
+    ("Odin", SplitType::Odin),
+    ("Level7", SplitType::Level7),
+    ("Level8", SplitType::Level8),
+    ("Level9", SplitType::Level9),
+    ("Diablo", SplitType::Diablo),
+    ("CowLevel", SplitType::CowLevel),
+    ("ExpGained", SplitType::ExpGained),
+    ("RawLevelComplete", SplitType::RawLevelComplete),
+    ("LevelComplete", SplitType::LevelComplete),
+    ("BossComplete", SplitType::BossComplete),
+    ("Bot2000Cube", SplitType::Bot2000Cube),
+    ("OdinCube", SplitType::OdinCube),
+    ("PadCrossed", SplitType::PadCrossed),
+];
+
+/// Error from parsing a split type from text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseSplitError {
+    Empty,
+    UnknownSplit,
+    WrongArgumentCount,
+    InvalidArgument,
+    UnbalancedParentheses,
+    TooManySplits,
+}
+
+impl fmt::Display for ParseSplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseSplitError::Empty => "missing split",
+            ParseSplitError::UnknownSplit => "unknown split",
+            ParseSplitError::WrongArgumentCount => "wrong number of arguments",
+            ParseSplitError::InvalidArgument => "invalid argument",
+            ParseSplitError::UnbalancedParentheses => "unbalanced parentheses",
+            ParseSplitError::TooManySplits => "too many splits",
+        })
+    }
+}
+
+/// Iterator over text separated by commas, skipping the commas nested in parentheses
+pub struct TopLevelItems<'a> {
+    rest: Option<&'a str>,
+}
+
+impl<'a> Iterator for TopLevelItems<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest?;
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    self.rest = Some(&rest[i + 1..]);
+                    return Some(rest[..i].trim());
+                }
+                _ => (),
+            }
+        }
+        self.rest = None;
+        Some(rest.trim())
+    }
+}
+
+/// Splits the text at the commas that are not nested in parentheses, trimming each item.
+/// Blank text has no items.
+pub fn top_level_items(text: &str) -> TopLevelItems<'_> {
+    TopLevelItems {
+        rest: Some(text).filter(|text| !text.trim().is_empty()),
+    }
+}
+
+/// Splits `Name(arguments)` into the name and the arguments, if there are any
+pub fn parse_term(text: &str) -> Result<(&str, Option<&str>), ParseSplitError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ParseSplitError::Empty);
+    }
+    let Some(open) = text.find('(') else {
+        return if text.contains(')') {
+            Err(ParseSplitError::UnbalancedParentheses)
+        } else {
+            Ok((text, None))
+        };
+    };
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        // The parentheses opened after the name must close exactly at the end
+        if depth == 0 && open + i != text.len() - 1 {
+            return Err(ParseSplitError::UnbalancedParentheses);
+        }
+    }
+    if depth != 0 {
+        return Err(ParseSplitError::UnbalancedParentheses);
+    }
+    Ok((text[..open].trim(), Some(&text[open + 1..text.len() - 1])))
+}
+
+impl fmt::Display for SplitType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitType::PadCrossedForLevel { raw_level } => {
+                write!(f, "PadCrossedForLevel({raw_level})")
+            }
+            SplitType::CompleteForLevel { raw_level } => write!(f, "CompleteForLevel({raw_level})"),
+            SplitType::PadCrossedForLevelOnLoop {
+                raw_level,
+                loop_num,
+            } => write!(f, "PadCrossedForLevelOnLoop({raw_level}, {loop_num})"),
+            SplitType::CompleteForLevelOnLoop {
+                raw_level,
+                loop_num,
+            } => write!(f, "CompleteForLevelOnLoop({raw_level}, {loop_num})"),
+            SplitType::PadsCrossed { num } => write!(f, "PadsCrossed({num})"),
+            SplitType::EnergyCubes { num } => write!(f, "EnergyCubes({num})"),
+            SplitType::Events { event, num } => write!(f, "Events({}, {num})", event.name()),
+            SplitType::Custom { index } => write!(f, "Custom({index})"),
+            SplitType::AllOf { group } => write!(f, "AllOf({group})"),
+            SplitType::AnyOf { group } => write!(f, "AnyOf({group})"),
+            SplitType::Sequence { group } => write!(f, "Sequence({group})"),
+            _ => {
+                let (name, _) = UNIT_SPLITS
+                    .iter()
+                    .find(|(_, split)| split == self)
+                    .ok_or(fmt::Error)?;
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl FromStr for SplitType {
+    type Err = ParseSplitError;
+
+    /// Parses a single split, as written by its `Display` implementation.
+    /// Combinators refer to their group by index here, see `Route` for writing their members out instead.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, arguments) = parse_term(text)?;
+        if arguments.is_none() {
+            if let Some((_, split)) = UNIT_SPLITS.iter().find(|(unit, _)| *unit == name) {
+                return Ok(*split);
+            }
+        }
+        let mut items = top_level_items(arguments.unwrap_or_default());
+        let mut next = || items.next().ok_or(ParseSplitError::WrongArgumentCount);
+        let int = |item: &str| {
+            item.parse::<i32>()
+                .map_err(|_| ParseSplitError::InvalidArgument)
+        };
+        let split = match name {
+            "PadCrossedForLevel" => SplitType::PadCrossedForLevel {
+                raw_level: int(next()?)?,
+            },
+            "CompleteForLevel" => SplitType::CompleteForLevel {
+                raw_level: int(next()?)?,
+            },
+            "PadCrossedForLevelOnLoop" => SplitType::PadCrossedForLevelOnLoop {
+                raw_level: int(next()?)?,
+                loop_num: int(next()?)?,
+            },
+            "CompleteForLevelOnLoop" => SplitType::CompleteForLevelOnLoop {
+                raw_level: int(next()?)?,
+                loop_num: int(next()?)?,
+            },
+            "PadsCrossed" => SplitType::PadsCrossed { num: int(next()?)? },
+            "EnergyCubes" => SplitType::EnergyCubes { num: int(next()?)? },
+            "Events" => SplitType::Events {
+                event: EventKind::from_name(next()?).ok_or(ParseSplitError::InvalidArgument)?,
+                num: int(next()?)?,
+            },
+            "Custom" => SplitType::Custom {
+                index: int(next()?)?,
+            },
+            "AllOf" => SplitType::AllOf {
+                group: int(next()?)?,
+            },
+            "AnyOf" => SplitType::AnyOf {
+                group: int(next()?)?,
+            },
+            "Sequence" => SplitType::Sequence {
+                group: int(next()?)?,
+            },
+            _ => return Err(ParseSplitError::UnknownSplit),
+        };
+        if items.next().is_some() {
+            return Err(ParseSplitError::WrongArgumentCount);
+        }
+        Ok(split)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use asr::arrayvec::ArrayString;
+
+    use super::*;
+
+    /// Every level in the order a run goes through them
+    const LEVELS: [SplitType; 13] = [
+        SplitType::Level1,
+        SplitType::Level2,
+        SplitType::Level3,
+        SplitType::Bot2000,
+        SplitType::Level4,
+        SplitType::Level5,
+        SplitType::Level6,
+        SplitType::Odin,
+        SplitType::Level7,
+        SplitType::Level8,
+        SplitType::Level9,
+        SplitType::Diablo,
+        SplitType::CowLevel,
+    ];
+
+    #[test]
+    fn next_goes_through_the_levels_and_loops_after_the_cow_level() {
+        for (level, next) in LEVELS.iter().zip(LEVELS.iter().cycle().skip(1)) {
+            assert_eq!(level.next(), Some(*next), "after {level:?}");
+            assert_eq!(next.raw_level(), Some(level.raw_level().unwrap() % 13 + 1));
+        }
+        assert_eq!(SplitType::Diablo.next(), Some(SplitType::CowLevel));
+        assert_eq!(SplitType::CowLevel.next(), Some(SplitType::Level1));
+        for split in [
+            SplitType::Manual,
+            SplitType::PadCrossed,
+            SplitType::CompleteForLevel { raw_level: 13 },
+        ] {
+            assert_eq!(split.next(), None, "after {split:?}");
+        }
+    }
+
+    #[test]
+    fn only_levels_with_pads_have_a_pad_count() {
+        for level in LEVELS {
+            let expected = match level {
+                SplitType::Bot2000 | SplitType::Odin | SplitType::Diablo | SplitType::CowLevel => {
+                    None
+                }
+                _ => Some(PAD_COUNT),
+            };
+            assert_eq!(level.pad_count(), expected, "{level:?}");
+            // Every level is completed either by its pads or by its boss
+            assert_ne!(
+                level.pad_count().is_some(),
+                level.is_boss_level(),
+                "{level:?}"
+            );
+        }
+        assert_eq!(SplitType::PadCrossed.pad_count(), None);
+    }
+
+    /// Splits with options, covering every one of them
+    const SPLITS_WITH_OPTIONS: [SplitType; 10] = [
+        SplitType::PadCrossedForLevel { raw_level: 1 },
+        SplitType::CompleteForLevel { raw_level: 13 },
+        SplitType::PadCrossedForLevelOnLoop {
+            raw_level: 9,
+            loop_num: 2,
+        },
+        SplitType::CompleteForLevelOnLoop {
+            raw_level: 12,
+            loop_num: 3,
+        },
+        SplitType::PadsCrossed { num: 5 },
+        SplitType::EnergyCubes { num: 3 },
+        SplitType::Custom { index: 0 },
+        SplitType::AllOf { group: 0 },
+        SplitType::AnyOf { group: 1 },
+        SplitType::Sequence { group: 15 },
+    ];
+
+    /// Returns true if the split is one of those the round trip goes through.
+    /// There is no catch-all arm, so a new split type does not build until it is added.
+    fn round_tripped(split: SplitType) -> bool {
+        match split {
+            SplitType::Manual
+            | SplitType::Level1
+            | SplitType::Level2
+            | SplitType::Level3
+            | SplitType::Bot2000
+            | SplitType::Level4
+            | SplitType::Level5
+            | SplitType::Level6
+            | SplitType::Odin
+            | SplitType::Level7
+            | SplitType::Level8
+            | SplitType::Level9
+            | SplitType::Diablo
+            | SplitType::CowLevel
+            | SplitType::ExpGained
+            | SplitType::RawLevelComplete
+            | SplitType::LevelComplete
+            | SplitType::BossComplete
+            | SplitType::Bot2000Cube
+            | SplitType::OdinCube
+            | SplitType::PadCrossed => UNIT_SPLITS.iter().any(|(_, unit)| *unit == split),
+            SplitType::Events { .. } => true,
+            SplitType::PadCrossedForLevel { .. }
+            | SplitType::CompleteForLevel { .. }
+            | SplitType::PadCrossedForLevelOnLoop { .. }
+            | SplitType::CompleteForLevelOnLoop { .. }
+            | SplitType::PadsCrossed { .. }
+            | SplitType::EnergyCubes { .. }
+            | SplitType::Custom { .. }
+            | SplitType::AllOf { .. }
+            | SplitType::AnyOf { .. }
+            | SplitType::Sequence { .. } => SPLITS_WITH_OPTIONS.iter().any(|with_options| {
+                core::mem::discriminant(with_options) == core::mem::discriminant(&split)
+            }),
+        }
+    }
+
+    #[test]
+    fn every_split_reads_back_what_it_writes() {
+        let events = EventKind::ALL
+            .into_iter()
+            .map(|event| SplitType::Events { event, num: 2 });
+        let splits = UNIT_SPLITS
+            .iter()
+            .map(|(_, split)| *split)
+            .chain(SPLITS_WITH_OPTIONS)
+            .chain(events);
+        for split in splits {
+            assert!(round_tripped(split), "{split:?}");
+            let mut text = ArrayString::<64>::new();
+            write!(text, "{split}").unwrap();
+            assert_eq!(text.parse::<SplitType>(), Ok(split), "{text}");
+        }
+        // Every unit split is written under its own name
+        for (name, split) in UNIT_SPLITS {
+            let mut text = ArrayString::<64>::new();
+            write!(text, "{split}").unwrap();
+            assert_eq!(text.as_str(), name);
+        }
+    }
+
+    #[test]
+    fn reads_options_with_spaces_and_rejects_bad_ones() {
+        assert_eq!(
+            " PadCrossedForLevelOnLoop( 9 ,2 ) ".parse(),
+            Ok(SplitType::PadCrossedForLevelOnLoop {
+                raw_level: 9,
+                loop_num: 2
+            })
+        );
+        for (text, error) in [
+            ("", ParseSplitError::Empty),
+            ("Level10", ParseSplitError::UnknownSplit),
+            ("Level1(1)", ParseSplitError::UnknownSplit),
+            ("PadsCrossed", ParseSplitError::WrongArgumentCount),
+            ("PadsCrossed(1, 2)", ParseSplitError::WrongArgumentCount),
+            ("PadsCrossed(x)", ParseSplitError::InvalidArgument),
+            ("Events(Jump, 2)", ParseSplitError::InvalidArgument),
+            ("PadsCrossed(1", ParseSplitError::UnbalancedParentheses),
+        ] {
+            assert_eq!(text.parse::<SplitType>(), Err(error), "{text:?}");
+        }
+    }
 }
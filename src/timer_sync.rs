@@ -0,0 +1,115 @@
+use core::{cmp::Ordering, fmt::Write};
+
+use asr::{
+    arrayvec::ArrayString,
+    timer::{self, TimerState},
+};
+
+use crate::{log, route::Route};
+
+/// Timer variable that any mismatch between the splits in LiveSplit and the route is reported in
+const SEGMENT_WARNING_VARIABLE: &str = "Segment warning";
+
+/// Returns how the number of segments in the splits compares to the count given, or None if LiveSplit does not
+/// report them. There is no segment count in the timer API, but whether a segment has been split is None past the last
+/// one, so only the segments around the count are looked at.
+fn compare_segments(count: usize) -> Option<Ordering> {
+    timer::segment_splitted(0)?;
+    Some(match count {
+        0 => Ordering::Greater,
+        _ if timer::segment_splitted(count as u64 - 1).is_none() => Ordering::Less,
+        _ if timer::segment_splitted(count as u64).is_some() => Ordering::Greater,
+        _ => Ordering::Equal,
+    })
+}
+
+/// Returns whether there is a run in progress, or None if the state is unknown
+fn timer_running() -> Option<bool> {
+    match timer::state() {
+        TimerState::Running | TimerState::Paused | TimerState::Ended => Some(true),
+        TimerState::NotRunning => Some(false),
+        _ => None,
+    }
+}
+
+/// Changes made to the run in LiveSplit, rather than by the splitter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerEvent {
+    /// The timer was reset
+    Reset,
+    /// The current segment is not the current split of the route, because of a manual split, skip or undo
+    MovedTo(usize),
+}
+
+/// Watches the LiveSplit timer so that the route can follow what the runner does in LiveSplit
+#[derive(Default)]
+pub struct TimerSync {
+    running: bool,
+    /// Segment that the splitter's own split moved LiveSplit to, which LiveSplit may only report on the next tick
+    moved_to: Option<usize>,
+}
+
+impl TimerSync {
+    /// Notes that the splitter itself moved the run to the split, so that LiveSplit still being on the one before it
+    /// for a tick is not taken for the runner undoing the split
+    pub fn splitter_moved_to(&mut self, split_index: usize) {
+        self.moved_to = Some(split_index);
+    }
+    /// Polls the timer, returning how the route should change to match it.
+    /// The splits are checked against the route whenever the timer starts, however it was started.
+    pub fn update(&mut self, route: &Route, split_index: usize) -> Option<TimerEvent> {
+        let moved_to = self.moved_to.take();
+        let running = timer_running()?;
+        let was_running = core::mem::replace(&mut self.running, running);
+        if !running {
+            return was_running.then_some(TimerEvent::Reset);
+        }
+        if !was_running {
+            check_segments(route, split_index);
+        }
+        let index = timer::current_split_index()? as usize;
+        // Right after the splitter's own split LiveSplit may not have caught up yet, so it is only followed from the
+        // tick after, when it is still somewhere else because it did not take the split
+        if moved_to.is_some_and(|moved_to| moved_to != index) {
+            return None;
+        }
+        (index != split_index).then_some(TimerEvent::MovedTo(index))
+    }
+}
+
+/// Compares the number of segments and the current split in LiveSplit with the route, warning if they differ.
+/// The timer API has no segment names, so nothing more than that can be checked.
+fn check_segments(route: &Route, split_index: usize) {
+    let mut warning = ArrayString::<256>::new();
+    let splits = route.splits().len();
+    match compare_segments(splits) {
+        Some(Ordering::Less) => {
+            let _ = write!(
+                warning,
+                "the splits have fewer segments than the {splits} splits of the route"
+            );
+        }
+        Some(Ordering::Greater) => {
+            let _ = write!(
+                warning,
+                "the splits have more segments than the {splits} splits of the route"
+            );
+        }
+        Some(Ordering::Equal) => match timer::current_split_index() {
+            Some(index) if index as usize != split_index => {
+                let _ = write!(
+                    warning,
+                    "LiveSplit is on segment {} but the route is on split {}",
+                    index + 1,
+                    split_index + 1
+                );
+            }
+            _ => (),
+        },
+        None => log!("LiveSplit did not report its segments, so they cannot be checked"),
+    }
+    if !warning.is_empty() {
+        log!("WARNING: {warning}, so splits will not line up with the segments!");
+    }
+    timer::set_variable(SEGMENT_WARNING_VARIABLE, &warning);
+}
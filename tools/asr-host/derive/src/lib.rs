@@ -0,0 +1,8 @@
+use proc_macro::TokenStream;
+
+/// There is no settings GUI on the host, so this only accepts the attributes of the asr derive.
+/// The tools fill in the settings map instead.
+#[proc_macro_derive(Gui, attributes(default, heading_level))]
+pub fn gui(_input: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
@@ -0,0 +1,327 @@
+//! A host implementation of the parts of the [asr](https://livesplit.org/asr/asr/) API that the auto splitter
+//! uses, so that its modules can run outside of LiveSplit. Only what the splitter calls is implemented, with the
+//! same signatures as asr, and anything LiveSplit would show is printed to stderr instead.
+
+mod process;
+mod runtime;
+
+pub use arrayvec;
+pub use process::{Address, Error, MemoryRange, MemoryRangeFlags, Process, ProcessId};
+pub use runtime::{future, run, set_tick_rate};
+
+/// Prints a message from the auto splitter
+pub fn print_message(text: &str) {
+    eprintln!("{text}");
+}
+
+pub mod time {
+    pub use time::Duration;
+}
+
+pub mod time_util {
+    use crate::time::Duration;
+
+    /// A point in time, measuring what has elapsed since in the same Duration as the rest of asr
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(std::time::Instant);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(std::time::Instant::now())
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Duration::try_from(self.0.elapsed()).unwrap_or(Duration::MAX)
+        }
+    }
+}
+
+pub mod watcher {
+    /// The previous and current values of something read from the game
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct Pair<T> {
+        pub old: T,
+        pub current: T,
+    }
+
+    impl<T: PartialEq> Pair<T> {
+        pub fn changed(&self) -> bool {
+            self.old != self.current
+        }
+    }
+}
+
+pub mod settings {
+    use std::{cell::RefCell, collections::BTreeMap, sync::Mutex};
+
+    use arrayvec::{ArrayString, CapacityError};
+
+    pub use asr_host_derive::Gui;
+
+    /// The settings map that LiveSplit would hold, which the tools fill in
+    static GLOBAL_MAP: Mutex<BTreeMap<String, Value>> = Mutex::new(BTreeMap::new());
+
+    /// A copy of the settings map, which is only shared once stored
+    #[derive(Debug, Clone, Default)]
+    pub struct Map(RefCell<BTreeMap<String, Value>>);
+
+    impl Map {
+        pub fn new() -> Self {
+            Self::default()
+        }
+        pub fn load() -> Self {
+            Self(RefCell::new(GLOBAL_MAP.lock().unwrap().clone()))
+        }
+        pub fn store(&self) {
+            *GLOBAL_MAP.lock().unwrap() = self.0.borrow().clone();
+        }
+        pub fn insert(&self, key: &str, value: &Value) {
+            self.0.borrow_mut().insert(key.into(), value.clone());
+        }
+        pub fn get(&self, key: &str) -> Option<Value> {
+            self.0.borrow().get(key).cloned()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        Bool(bool),
+        I64(i64),
+        String(String),
+    }
+
+    impl Value {
+        pub fn get_bool(&self) -> Option<bool> {
+            match self {
+                Value::Bool(value) => Some(*value),
+                _ => None,
+            }
+        }
+        pub fn get_i64(&self) -> Option<i64> {
+            match self {
+                Value::I64(value) => Some(*value),
+                _ => None,
+            }
+        }
+        pub fn get_array_string<const N: usize>(
+            &self,
+        ) -> Option<Result<ArrayString<N>, CapacityError>> {
+            match self {
+                Value::String(value) => Some(ArrayString::from(value).map_err(|e| e.simplify())),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<bool> for Value {
+        fn from(value: bool) -> Self {
+            Value::Bool(value)
+        }
+    }
+
+    impl From<i64> for Value {
+        fn from(value: i64) -> Self {
+            Value::I64(value)
+        }
+    }
+
+    impl From<&str> for Value {
+        fn from(value: &str) -> Self {
+            Value::String(value.into())
+        }
+    }
+}
+
+pub mod timer {
+    use std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        sync::Mutex,
+        time::{self as std_time, Instant},
+    };
+
+    use crate::time::Duration;
+
+    /// Game time as LiveSplit keeps it, running along with real time unless it is paused
+    struct GameTime {
+        /// Game time up to when it was last set, paused or resumed
+        base: Duration,
+        /// When game time last started running, or None while it is paused
+        running_since: Option<Instant>,
+    }
+
+    impl GameTime {
+        fn now(&self) -> Duration {
+            self.base
+                + self
+                    .running_since
+                    .map_or(std_time::Duration::ZERO, |since| since.elapsed())
+        }
+    }
+
+    static GAME_TIME: Mutex<GameTime> = Mutex::new(GameTime {
+        base: Duration::ZERO,
+        running_since: None,
+    });
+
+    /// Sets the game time, which then carries on running unless it is paused
+    pub fn set_game_time(time: Duration) {
+        let mut game_time = GAME_TIME.lock().unwrap();
+        game_time.base = time;
+        if game_time.running_since.is_some() {
+            game_time.running_since = Some(Instant::now());
+        }
+    }
+
+    pub fn pause_game_time() {
+        let mut game_time = GAME_TIME.lock().unwrap();
+        if game_time.running_since.is_some() {
+            game_time.base = game_time.now();
+            game_time.running_since = None;
+            eprintln!(
+                "[timer] game time paused at {:.3}s",
+                game_time.base.as_seconds_f64()
+            );
+        }
+    }
+
+    pub fn resume_game_time() {
+        let mut game_time = GAME_TIME.lock().unwrap();
+        if game_time.running_since.is_none() {
+            game_time.running_since = Some(Instant::now());
+            eprintln!(
+                "[timer] game time resumed at {:.3}s",
+                game_time.base.as_seconds_f64()
+            );
+        }
+    }
+
+    /// Returns the current game time.
+    /// This is only on the host, for the tools to read back what the splitter did with it.
+    pub fn game_time() -> Duration {
+        GAME_TIME.lock().unwrap().now()
+    }
+
+    /// State of the timer in LiveSplit
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum TimerState {
+        NotRunning,
+        Running,
+        Paused,
+        Ended,
+        Unknown,
+    }
+
+    /// The run as LiveSplit keeps it: its state, whether each segment has been split, and the current segment
+    struct Run {
+        state: TimerState,
+        splitted: Vec<bool>,
+        index: usize,
+    }
+
+    thread_local! {
+        // Each thread has its own run and variables, so that tests running at once do not share them
+        static VARIABLES: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+        static RUN: RefCell<Run> = const {
+            RefCell::new(Run {
+                state: TimerState::NotRunning,
+                splitted: Vec::new(),
+                index: 0,
+            })
+        };
+    }
+
+    /// Sets how many segments the splits have, resetting the run.
+    /// This is only on the host, standing in for the splits that are loaded in LiveSplit.
+    pub fn set_segments(count: usize) {
+        RUN.with_borrow_mut(|run| {
+            run.state = TimerState::NotRunning;
+            run.splitted = vec![false; count];
+            run.index = 0;
+        });
+    }
+
+    pub fn state() -> TimerState {
+        RUN.with_borrow(|run| run.state)
+    }
+
+    pub fn start() {
+        RUN.with_borrow_mut(|run| {
+            if run.state == TimerState::NotRunning && !run.splitted.is_empty() {
+                eprintln!("[timer] started");
+                run.state = TimerState::Running;
+                run.splitted.fill(false);
+                run.index = 0;
+            }
+        });
+    }
+
+    pub fn split() {
+        RUN.with_borrow_mut(|run| {
+            if run.state == TimerState::Running {
+                eprintln!("[timer] split segment {}", run.index + 1);
+                run.splitted[run.index] = true;
+                run.index += 1;
+                if run.index == run.splitted.len() {
+                    run.state = TimerState::Ended;
+                }
+            }
+        });
+    }
+
+    pub fn skip_split() {
+        RUN.with_borrow_mut(|run| {
+            if run.state == TimerState::Running && run.index + 1 < run.splitted.len() {
+                eprintln!("[timer] skipped segment {}", run.index + 1);
+                run.index += 1;
+            }
+        });
+    }
+
+    pub fn undo_split() {
+        RUN.with_borrow_mut(|run| {
+            if matches!(run.state, TimerState::Running | TimerState::Ended) && run.index > 0 {
+                run.index -= 1;
+                run.splitted[run.index] = false;
+                run.state = TimerState::Running;
+                eprintln!("[timer] undid segment {}", run.index + 1);
+            }
+        });
+    }
+
+    pub fn reset() {
+        RUN.with_borrow_mut(|run| {
+            if run.state != TimerState::NotRunning {
+                eprintln!("[timer] reset");
+                run.state = TimerState::NotRunning;
+                run.index = 0;
+            }
+        });
+    }
+
+    /// Returns the index of the current segment, or None if no run is in progress
+    pub fn current_split_index() -> Option<u64> {
+        RUN.with_borrow(|run| (run.state != TimerState::NotRunning).then_some(run.index as u64))
+    }
+
+    /// Returns whether the segment has been split, or None if there is no such segment
+    pub fn segment_splitted(idx: u64) -> Option<bool> {
+        RUN.with_borrow(|run| run.splitted.get(idx as usize).copied())
+    }
+
+    /// Sets a custom variable, printing it whenever its value changes from the last (or from empty)
+    pub fn set_variable(key: &str, value: &str) {
+        VARIABLES.with_borrow_mut(|variables| {
+            if variables.get(key).map_or("", String::as_str) != value {
+                eprintln!("[variable] {key} = {value}");
+                variables.insert(key.into(), value.into());
+            }
+        });
+    }
+
+    /// Returns the current value of a custom variable.
+    /// This is only on the host, for the tools to read back what the splitter reported.
+    pub fn variable(key: &str) -> Option<String> {
+        VARIABLES.with_borrow(|variables| variables.get(key).cloned())
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File},
+    future::{poll_fn, Future},
+    mem::{self, ManuallyDrop},
+    num::NonZeroU64,
+    ops::{Add, BitOr},
+    os::unix::fs::FileExt,
+    path::Path,
+    pin::pin,
+    slice,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    task::Poll,
+};
+
+use arrayvec::ArrayVec;
+use bytemuck::{CheckedBitPattern, Zeroable};
+
+use crate::future::next_tick;
+
+pub type ProcessId = u64;
+
+/// Any failure to read from a process or its memory ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error;
+
+/// An address in the memory of a process
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Address(u64);
+
+impl Address {
+    pub const NULL: Self = Self(0);
+
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+    // Named as in asr, alongside the trait
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, offset: u64) -> Self {
+        Self(self.0.wrapping_add(offset))
+    }
+    pub fn add_signed(self, offset: i64) -> Self {
+        Self(self.0.wrapping_add_signed(offset))
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:X}", self.0)
+    }
+}
+
+impl From<u64> for Address {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl Add<u64> for Address {
+    type Output = Self;
+
+    fn add(self, offset: u64) -> Self {
+        Address::add(self, offset)
+    }
+}
+
+/// Permissions of a memory range, as in the maps of a process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryRangeFlags(u8);
+
+impl MemoryRangeFlags {
+    pub const READ: Self = Self(1);
+    pub const WRITE: Self = Self(1 << 1);
+    pub const EXECUTE: Self = Self(1 << 2);
+    /// The range is mapped from a file
+    pub const PATH: Self = Self(1 << 3);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for MemoryRangeFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// A mapped range of memory in a process, from a line of `/proc/<pid>/maps`
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryRange {
+    address: Address,
+    size: u64,
+    flags: MemoryRangeFlags,
+}
+
+impl MemoryRange {
+    /// Parses a line of the maps of a process, such as `7f0000000000-7f0000010000 rw-p 00000000 00:00 0   [heap]`.
+    /// This is only on the host, for the tools to read recorded maps.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut columns = line.split_whitespace();
+        let (start, end) = columns.next()?.split_once('-')?;
+        let start = u64::from_str_radix(start, 16).ok()?;
+        let end = u64::from_str_radix(end, 16).ok()?;
+        let permissions = columns.next()?.as_bytes();
+        let mut flags = MemoryRangeFlags::default();
+        for (index, flag) in [
+            MemoryRangeFlags::READ,
+            MemoryRangeFlags::WRITE,
+            MemoryRangeFlags::EXECUTE,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if permissions.get(index).is_some_and(|c| *c != b'-') {
+                flags = flags | flag;
+            }
+        }
+        // The path is the sixth column, after the offset, device and inode
+        if columns.nth(3).is_some_and(|path| path.starts_with('/')) {
+            flags = flags | MemoryRangeFlags::PATH;
+        }
+        Some(Self {
+            address: Address(start),
+            size: end.checked_sub(start)?,
+            flags,
+        })
+    }
+    pub fn range(&self) -> Result<(Address, u64), Error> {
+        Ok((self.address, self.size))
+    }
+    pub fn address(&self) -> Result<Address, Error> {
+        Ok(self.address)
+    }
+    pub fn size(&self) -> Result<u64, Error> {
+        Ok(self.size)
+    }
+    pub fn flags(&self) -> Result<MemoryRangeFlags, Error> {
+        Ok(self.flags)
+    }
+}
+
+struct OpenProcess {
+    pid: ProcessId,
+    mem: File,
+}
+
+/// Processes are handles into this table, like in the runtime, so that copies of a handle can be passed to
+/// `process_read` the way `sigscan.rs` does
+static OPEN_PROCESSES: Mutex<BTreeMap<u64, OpenProcess>> = Mutex::new(BTreeMap::new());
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// A process attached through `/proc/<pid>/mem`, which needs permission to trace the process.
+/// That means running as the same user with `kernel.yama.ptrace_scope` set to 0, or as root.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Process(NonZeroU64);
+
+/// Returns true if the process has the name, either as its command or as the file name of its first argument.
+/// The second catches Windows programs running under Wine, where the command is the Wine loader.
+fn has_name(pid: ProcessId, name: &str) -> bool {
+    let proc_path = Path::new("/proc").join(pid.to_string());
+    if fs::read_to_string(proc_path.join("comm")).is_ok_and(|comm| comm.trim_end() == name) {
+        return true;
+    }
+    fs::read(proc_path.join("cmdline")).is_ok_and(|cmdline| {
+        let program = cmdline.split(|c| *c == 0).next().unwrap_or_default();
+        let program = String::from_utf8_lossy(program);
+        program.rsplit(['/', '\\']).next() == Some(name)
+    })
+}
+
+impl Process {
+    /// Returns the ids of all processes with the name
+    pub fn list_by_name<const N: usize>(name: &str) -> Option<ArrayVec<ProcessId, N>> {
+        let entries = fs::read_dir("/proc").ok()?;
+        let mut pids: Vec<ProcessId> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|pid| has_name(*pid, name))
+            .collect();
+        pids.sort_unstable();
+        pids.truncate(N);
+        Some(pids.into_iter().collect())
+    }
+    pub fn attach(name: &str) -> Option<Self> {
+        Self::list_by_name::<64>(name)?
+            .into_iter()
+            .find_map(Self::attach_by_pid)
+    }
+    pub fn attach_by_pid(pid: ProcessId) -> Option<Self> {
+        let mem = File::open(format!("/proc/{pid}/mem")).ok()?;
+        let handle = NonZeroU64::new(NEXT_HANDLE.fetch_add(1, Ordering::Relaxed))?;
+        OPEN_PROCESSES
+            .lock()
+            .unwrap()
+            .insert(handle.get(), OpenProcess { pid, mem });
+        Some(Self(handle))
+    }
+    pub async fn wait_attach(name: &str) -> Self {
+        loop {
+            if let Some(process) = Self::attach(name) {
+                return process;
+            }
+            next_tick().await;
+        }
+    }
+    /// Returns the id of the process. This is only on the host, for the tools to report which process they attached to.
+    pub fn pid(&self) -> ProcessId {
+        self.with_open(|open| open.pid).unwrap_or_default()
+    }
+    fn with_open<T>(&self, f: impl FnOnce(&OpenProcess) -> T) -> Option<T> {
+        OPEN_PROCESSES.lock().unwrap().get(&self.0.get()).map(f)
+    }
+    /// Returns the path of the executable, which under Wine is Wine's own rather than the game's
+    pub fn get_path(&self) -> Result<String, Error> {
+        let path = fs::read_link(format!("/proc/{}/exe", self.pid())).map_err(|_| Error)?;
+        path.into_os_string().into_string().map_err(|_| Error)
+    }
+    pub fn is_open(&self) -> bool {
+        // A process that has exited but not been reaped yet is a zombie, with no memory left to read
+        fs::read_to_string(format!("/proc/{}/stat", self.pid())).is_ok_and(|stat| {
+            stat.rsplit(')')
+                .next()
+                .is_some_and(|rest| !rest.starts_with(" Z"))
+        })
+    }
+    pub fn read_into_buf(&self, address: impl Into<Address>, buf: &mut [u8]) -> Result<(), Error> {
+        let address = address.into();
+        self.with_open(|open| open.mem.read_exact_at(buf, address.value()))
+            .ok_or(Error)?
+            .map_err(|_| Error)
+    }
+    pub fn read<T: CheckedBitPattern>(&self, address: impl Into<Address>) -> Result<T, Error> {
+        let mut bits = T::Bits::zeroed();
+        // SAFETY: The bits are valid for any bytes, so they can be read into as bytes
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(
+                (&mut bits as *mut T::Bits).cast::<u8>(),
+                size_of::<T::Bits>(),
+            )
+        };
+        self.read_into_buf(address, bytes)?;
+        if !T::is_valid_bit_pattern(&bits) {
+            return Err(Error);
+        }
+        // SAFETY: The bits were checked to be a valid T, which has the same layout
+        Ok(unsafe { mem::transmute_copy(&bits) })
+    }
+    /// Returns the memory ranges from the maps of the process, as they are when this is called
+    pub fn memory_ranges(&self) -> impl DoubleEndedIterator<Item = MemoryRange> + '_ {
+        fs::read_to_string(format!("/proc/{}/maps", self.pid()))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(MemoryRange::parse)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+    /// Runs the future until it finishes, or until the process closes
+    pub async fn until_closes<F: Future>(&self, future: F) -> Result<F::Output, Error> {
+        let mut future = pin!(future);
+        poll_fn(|cx| {
+            if !self.is_open() {
+                return Poll::Ready(Err(Error));
+            }
+            future.as_mut().poll(cx).map(Ok)
+        })
+        .await
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        OPEN_PROCESSES.lock().unwrap().remove(&self.0.get());
+    }
+}
+
+/// The runtime's read of process memory, which `sigscan.rs` calls directly to avoid asr's overhead.
+/// The handle is only a copy, so it must not be dropped here.
+///
+/// # Safety
+/// The buffer must be valid for writes of its length.
+#[no_mangle]
+pub unsafe extern "C" fn process_read(
+    process: ManuallyDrop<Process>,
+    address: Address,
+    buf_ptr: *mut u8,
+    buf_len: usize,
+) -> bool {
+    let buf = unsafe { slice::from_raw_parts_mut(buf_ptr, buf_len) };
+    process.read_into_buf(address, buf).is_ok()
+}
@@ -0,0 +1,64 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Ticks per second, stored as the bits of an f64. The runtime starts at 120 ticks per second.
+static TICK_RATE: AtomicU64 = AtomicU64::new(0x405E000000000000);
+
+/// Sets how often the splitter is polled per second
+pub fn set_tick_rate(ticks_per_second: f64) {
+    TICK_RATE.store(ticks_per_second.to_bits(), Ordering::Relaxed);
+}
+
+fn tick_duration() -> Duration {
+    Duration::from_secs_f64(1.0 / f64::from_bits(TICK_RATE.load(Ordering::Relaxed)))
+}
+
+pub mod future {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    /// Waits until the next tick, like asr's
+    pub fn next_tick() -> NextTick {
+        NextTick { ticked: false }
+    }
+
+    pub struct NextTick {
+        ticked: bool,
+    }
+
+    impl Future for NextTick {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.ticked {
+                Poll::Ready(())
+            } else {
+                self.ticked = true;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Drives a future the way the runtime drives the splitter's main, polling it once per tick until it finishes.
+/// This is only on the host, where there is no runtime to do it.
+pub fn run<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        let tick_start = Instant::now();
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::sleep(tick_duration().saturating_sub(tick_start.elapsed()));
+    }
+}
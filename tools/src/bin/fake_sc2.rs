@@ -0,0 +1,361 @@
+//! A stand-in for SC2 to scan, on Linux. It maps many read/write ranges at addresses like SC2's under Windows,
+//! plants the exp signature in one of them and then changes the exp as a script says.
+//!
+//! Usage: `fake_sc2 [--ranges N] [--target INDEX] [--offset BYTES] [--range-size BYTES] [--wine] [--speed SPEED]
+//! [--decoys RATES] [SCRIPT]`
+//!
+//! With `--wine`, the ranges other than the target are between 4 KiB and 60 KiB like under Wine, instead of 64 KiB.
+//!
+//! Once the ranges are mapped, the address of the exp is printed, then the script runs when a line is read from
+//! stdin. The script is a comma separated list of `set EXP`, `gain EXP`, `wait MILLISECONDS`, `leave`, `join`,
+//! `pause`, `unpause`, `speed SPEED`, `die` and `revive`, and the process exits after the last step. If the script
+//! starts with a `set`, the exp starts there rather than at 0. `leave` clears the exp signature like leaving the
+//! game does, and `join` puts it back like starting a new game. `die` and `revive` clear and set the alive flag of
+//! the runling.
+//!
+//! A game loop counter is kept a little after the exp, which advances while the script runs, except between `pause`
+//! and `unpause`. It runs at the Faster game speed unless `--speed` gives another one of `slower`, `slow`, `normal`,
+//! `fast` or `faster`, and `speed` changes it like the game's options do. Further on there is a copy of it, and
+//! before it a frame counter that advances 30 times a second whether or not the game is paused, which the game loop
+//! has to be told apart from. `--decoys` adds more counters like it, such as render ticks, advancing at each of a
+//! comma separated list of rates per second. Like in SC2, the exp and the alive flag only change on a step of the
+//! game loop, so each `set`, `gain`, `die` or `revive` is made on the next step, and is printed to stderr along with
+//! the game loop it was made on.
+//! The tests in `tests` run this.
+
+use std::{
+    env, io,
+    process::ExitCode,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rlr_tools::game_clock::GameSpeed;
+
+const USAGE: &str = "Usage: fake_sc2 [--ranges N] [--target INDEX] [--offset BYTES] [--range-size BYTES] [--wine] [--speed SPEED] [--decoys RATES] [SCRIPT]";
+/// Where the ranges start, which is inside the window `find_exp_pattern` scans
+const BASE_ADDRESS: usize = 0x200000000;
+/// The exp signature as it is in memory, with the exp itself right before it
+const EXP_SIGNATURE: u128 = 0x00000000000110CA00011BDF0000004A;
+/// Where the alive flag is kept, from the exp. This is where the splitter looks for it, but is written out here rather
+/// than taken from it, so that the tests check the splitter against this layout.
+const ALIVE_OFFSET: usize = 0x14;
+/// Where the game loop and a copy of it are kept, from the exp
+const GAME_LOOP_OFFSET: usize = 0x100;
+const GAME_LOOP_COPY_OFFSET: usize = 0x180;
+/// Where the frame counter is kept, from the exp, and how many times a second it advances. It is before the game
+/// loop, so that it would be found first if it were taken for it.
+const FRAME_COUNTER_OFFSET: usize = 0x40;
+const FRAMES_PER_SECOND: f64 = 30.0;
+/// Where the decoy counters are kept, from the exp, between the frame counter and the game loop
+const DECOYS_OFFSET: usize = 0x80;
+const MAX_DECOYS: usize = 16;
+const DEFAULT_SCRIPT: &str = "set 1000, wait 1000, gain 18, wait 250, gain 18, wait 250";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Set(i32),
+    Gain(i32),
+    Wait(u64),
+    Leave,
+    Join,
+    Pause,
+    Unpause,
+    Speed(GameSpeed),
+    Die,
+    Revive,
+}
+
+fn parse_script(script: &str) -> Option<Vec<Step>> {
+    script
+        .split(',')
+        .map(|step| {
+            match step.trim() {
+                "leave" => return Some(Step::Leave),
+                "join" => return Some(Step::Join),
+                "pause" => return Some(Step::Pause),
+                "unpause" => return Some(Step::Unpause),
+                "die" => return Some(Step::Die),
+                "revive" => return Some(Step::Revive),
+                _ => (),
+            }
+            let (command, value) = step.trim().split_once(' ')?;
+            let value = value.trim();
+            Some(match command {
+                "set" => Step::Set(value.parse().ok()?),
+                "gain" => Step::Gain(value.parse().ok()?),
+                "wait" => Step::Wait(value.parse().ok()?),
+                "speed" => Step::Speed(parse_speed(value)?),
+                _ => return None,
+            })
+        })
+        .collect()
+}
+
+fn parse_speed(speed: &str) -> Option<GameSpeed> {
+    Some(match speed {
+        "slower" => GameSpeed::Slower,
+        "slow" => GameSpeed::Slow,
+        "normal" => GameSpeed::Normal,
+        "fast" => GameSpeed::Fast,
+        "faster" => GameSpeed::Faster,
+        _ => return None,
+    })
+}
+
+struct Options {
+    ranges: usize,
+    target: usize,
+    offset: usize,
+    range_size: usize,
+    wine: bool,
+    game_speed: GameSpeed,
+    decoys: Vec<f64>,
+    script: Vec<Step>,
+}
+
+fn parse_options() -> Option<Options> {
+    let mut options = Options {
+        // These come first in the maps, being below the program and its libraries.
+        // The scan skips the first 1000 ranges, so the target has to be past them.
+        ranges: 1200,
+        target: 1100,
+        offset: 0x2000,
+        range_size: 64 << 10,
+        wine: false,
+        game_speed: GameSpeed::Faster,
+        decoys: Vec::new(),
+        script: parse_script(DEFAULT_SCRIPT)?,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next()?.parse().ok();
+        match arg.as_str() {
+            "--ranges" => options.ranges = value()?,
+            "--target" => options.target = value()?,
+            "--offset" => options.offset = value()?,
+            "--range-size" => options.range_size = value()?,
+            "--wine" => options.wine = true,
+            "--speed" => options.game_speed = parse_speed(&args.next()?)?,
+            "--decoys" => {
+                options.decoys = args
+                    .next()?
+                    .split(',')
+                    .map(|rate| rate.trim().parse().ok())
+                    .collect::<Option<_>>()?
+            }
+            script => options.script = parse_script(script)?,
+        }
+    }
+    // The exp comes 4 bytes before the signature, all within the target range and aligned like in SC2,
+    // followed by the alive flag, the frame counter, the decoys, and the game loop and its copy
+    let fits = options.offset >= 4
+        && options.offset.is_multiple_of(4)
+        && options.offset + GAME_LOOP_COPY_OFFSET + 4 <= options.range_size;
+    (options.target < options.ranges && fits && options.decoys.len() <= MAX_DECOYS)
+        .then_some(options)
+}
+
+/// What the script changes, for the game loop to make on its next step
+struct Game {
+    paused: AtomicBool,
+    /// Loops per second of the game speed, as the bits of an `f64`
+    loops_per_second: AtomicU64,
+    exp: AtomicI32,
+    alive: AtomicBool,
+}
+
+/// Advances the game loop at the pointer at the game speed, except while paused, writing any change to the exp or the
+/// alive flag on each step. Both come after the exp in the target range, which is never unmapped.
+fn run_game_loop(exp_ptr: usize, game: &Game) {
+    let exp_ptr = exp_ptr as *mut i32;
+    // SAFETY: The game loop and its copy were checked to fit in the target range
+    let game_loop_ptr = unsafe { exp_ptr.byte_add(GAME_LOOP_OFFSET) }.cast::<u32>();
+    let game_loop_copy_ptr = unsafe { exp_ptr.byte_add(GAME_LOOP_COPY_OFFSET) }.cast::<u32>();
+    let alive_ptr = unsafe { exp_ptr.byte_add(ALIVE_OFFSET) }.cast::<u32>();
+    let mut due = Instant::now();
+    let mut game_loop = 0u32;
+    let mut exp = game.exp.load(Ordering::Relaxed);
+    let mut alive = true;
+    loop {
+        // Sleep until the step is due rather than for a fixed time, so the rate does not drift
+        let loops_per_second = f64::from_bits(game.loops_per_second.load(Ordering::Relaxed));
+        due += Duration::from_secs_f64(1.0 / loops_per_second);
+        thread::sleep(due.saturating_duration_since(Instant::now()));
+        if game.paused.load(Ordering::Relaxed) {
+            continue;
+        }
+        game_loop += 1;
+        // SAFETY: These are all in the target range
+        unsafe {
+            game_loop_ptr.write_volatile(game_loop);
+            game_loop_copy_ptr.write_volatile(game_loop);
+        }
+        let new_exp = game.exp.load(Ordering::Relaxed);
+        if new_exp != exp {
+            exp = new_exp;
+            // Exp is stored as a multiple of 4096
+            unsafe { exp_ptr.write_volatile(exp * 4096) };
+            eprintln!("exp: {exp} at loop {game_loop}");
+        }
+        let new_alive = game.alive.load(Ordering::Relaxed);
+        if new_alive != alive {
+            alive = new_alive;
+            unsafe { alive_ptr.write_volatile(alive as u32) };
+            let change = if alive { "revived" } else { "died" };
+            eprintln!("{change} at loop {game_loop}");
+        }
+    }
+}
+
+/// Advances a counter at the offset from the exp pointer at the rate per second, paused or not, like the frame counter
+fn run_counter(exp_ptr: usize, offset: usize, per_second: f64) {
+    // SAFETY: The frame counter and the decoys are before the game loop, which was checked to fit in the target range
+    let counter_ptr = unsafe { (exp_ptr as *mut i32).byte_add(offset) }.cast::<u32>();
+    let start = Instant::now();
+    for count in 1.. {
+        let due = start + Duration::from_secs_f64(count as f64 / per_second);
+        thread::sleep(due.saturating_duration_since(Instant::now()));
+        unsafe { counter_ptr.write_volatile(count) };
+    }
+}
+
+/// Maps a read/write range at the address, failing instead of replacing anything already there
+fn map_range(address: usize, size: usize) -> io::Result<*mut u8> {
+    // SAFETY: Nothing can be mapped over, and the new range is only accessed through the returned pointer
+    let mapped = unsafe {
+        libc::mmap(
+            address as *mut libc::c_void,
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+            -1,
+            0,
+        )
+    };
+    if mapped == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    if mapped as usize != address {
+        // Older kernels treat the address as a hint
+        return Err(io::Error::other("the range was mapped somewhere else"));
+    }
+    Ok(mapped.cast())
+}
+
+fn main() -> ExitCode {
+    let Some(options) = parse_options() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let mut target = ptr::null_mut();
+    let mut address = BASE_ADDRESS;
+    for index in 0..options.ranges {
+        let size = if options.wine && index != options.target {
+            (1 + index % 15) * (4 << 10)
+        } else {
+            options.range_size
+        };
+        let range = map_range(address, size);
+        // Leave a gap after every range so the kernel keeps them as separate ranges
+        address += size.max(options.range_size) * 2;
+        match range {
+            Ok(range) if index == options.target => target = range,
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Could not map range {index}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // SAFETY: The signature and the exp before it were checked to fit in the target range
+    let (signature_ptr, exp_ptr) = unsafe {
+        let signature = target.add(options.offset);
+        (signature.cast::<u128>(), signature.sub(4).cast::<i32>())
+    };
+    let write_signature = |signature: u128| unsafe { signature_ptr.write_unaligned(signature) };
+    write_signature(EXP_SIGNATURE);
+    // Start at the first exp of the script, so the exp never jumps to it
+    let mut exp = match options.script.first() {
+        Some(Step::Set(value)) => *value,
+        _ => 0,
+    };
+    // Exp is stored as a multiple of 4096
+    unsafe { exp_ptr.write_volatile(exp * 4096) };
+    // The runling starts out alive
+    unsafe {
+        exp_ptr
+            .byte_add(ALIVE_OFFSET)
+            .cast::<u32>()
+            .write_volatile(1)
+    };
+    println!("exp address: {:#X}", exp_ptr as usize);
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line);
+    let game = Arc::new(Game {
+        paused: AtomicBool::new(false),
+        loops_per_second: AtomicU64::new(options.game_speed.loops_per_second().to_bits()),
+        exp: AtomicI32::new(exp),
+        alive: AtomicBool::new(true),
+    });
+    thread::spawn({
+        let exp_ptr = exp_ptr as usize;
+        move || run_counter(exp_ptr, FRAME_COUNTER_OFFSET, FRAMES_PER_SECOND)
+    });
+    for (i, &rate) in options.decoys.iter().enumerate() {
+        let exp_ptr = exp_ptr as usize;
+        thread::spawn(move || run_counter(exp_ptr, DECOYS_OFFSET + i * 4, rate));
+    }
+    thread::spawn({
+        let exp_ptr = exp_ptr as usize;
+        let game = game.clone();
+        move || run_game_loop(exp_ptr, &game)
+    });
+    for step in options.script {
+        match step {
+            Step::Set(value) => exp = value,
+            Step::Gain(value) => exp += value,
+            Step::Wait(milliseconds) => {
+                thread::sleep(Duration::from_millis(milliseconds));
+                continue;
+            }
+            Step::Pause | Step::Unpause => {
+                let pause = step == Step::Pause;
+                game.paused.store(pause, Ordering::Relaxed);
+                eprintln!("{}", if pause { "paused" } else { "unpaused" });
+                continue;
+            }
+            Step::Speed(game_speed) => {
+                let loops_per_second = game_speed.loops_per_second().to_bits();
+                game.loops_per_second
+                    .store(loops_per_second, Ordering::Relaxed);
+                eprintln!("game speed: {game_speed:?}");
+                continue;
+            }
+            Step::Die | Step::Revive => {
+                game.alive.store(step == Step::Revive, Ordering::Relaxed);
+                continue;
+            }
+            Step::Leave | Step::Join => {
+                let left = step == Step::Leave;
+                write_signature(if left { 0 } else { EXP_SIGNATURE });
+                eprintln!(
+                    "{}",
+                    if left {
+                        "left the game"
+                    } else {
+                        "joined a game"
+                    }
+                );
+                continue;
+            }
+        }
+        game.exp.store(exp, Ordering::Relaxed);
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,167 @@
+//! Writes a LiveSplit splits file for a route to stdout, with one segment per split in the order the splitter
+//! fires them.
+//!
+//! Usage: `lss <any | cow | ROUTE> [CUSTOM_SPLITS]`
+//!
+//! The route is either `any`, `cow` or a route such as `Level1, Bot2000, PadsCrossed(5)`, see `load_route`.
+
+use std::{env, fmt::Write, process::ExitCode};
+
+use rlr_tools::{route::Route, split_state::EventKind, split_type::SplitType};
+
+const USAGE: &str = "Usage: lss <any | cow | ROUTE> [CUSTOM_SPLITS]";
+
+/// Returns the name of a level as it is shown in game
+fn level_name(level: SplitType) -> &'static str {
+    match level {
+        SplitType::Level1 => "Level 1",
+        SplitType::Level2 => "Level 2",
+        SplitType::Level3 => "Level 3",
+        SplitType::Bot2000 => "Bot2000",
+        SplitType::Level4 => "Level 4",
+        SplitType::Level5 => "Level 5",
+        SplitType::Level6 => "Level 6",
+        SplitType::Odin => "Odin",
+        SplitType::Level7 => "Level 7",
+        SplitType::Level8 => "Level 8",
+        SplitType::Level9 => "Level 9",
+        SplitType::Diablo => "Diablo",
+        SplitType::CowLevel => "Cow Level",
+        _ => "Unknown level",
+    }
+}
+
+fn raw_level_name(raw_level: i32) -> &'static str {
+    SplitType::from_raw_level(raw_level).map_or("Unknown level", level_name)
+}
+
+fn event_name(event: EventKind) -> &'static str {
+    match event {
+        EventKind::Pad => "Pads",
+        EventKind::Cube => "Cubes",
+        EventKind::Boss => "Bosses",
+        EventKind::Level => "Levels",
+        EventKind::ExpGained => "Exp",
+        EventKind::DifficultyChange => "Difficulty changes",
+    }
+}
+
+/// Returns the segment name for a split, naming combinators after their members
+fn segment_name(route: &Route, split: SplitType) -> String {
+    match split {
+        SplitType::Manual => "Manual".into(),
+        SplitType::ExpGained => "Exp gained".into(),
+        SplitType::RawLevelComplete | SplitType::LevelComplete => "Level complete".into(),
+        SplitType::BossComplete => "Boss complete".into(),
+        SplitType::Bot2000Cube => "Bot2000 cubes".into(),
+        SplitType::OdinCube => "Odin cubes".into(),
+        SplitType::PadCrossed => "Pad".into(),
+        SplitType::PadCrossedForLevel { raw_level } => {
+            format!("{} pad", raw_level_name(raw_level))
+        }
+        SplitType::CompleteForLevel { raw_level } => {
+            format!("{} complete", raw_level_name(raw_level))
+        }
+        SplitType::PadCrossedForLevelOnLoop {
+            raw_level,
+            loop_num,
+        } => format!("{} pad (loop {loop_num})", raw_level_name(raw_level)),
+        SplitType::CompleteForLevelOnLoop {
+            raw_level,
+            loop_num,
+        } => format!("{} complete (loop {loop_num})", raw_level_name(raw_level)),
+        SplitType::PadsCrossed { num } => format!("Pads ×{num}"),
+        SplitType::EnergyCubes { num } => format!("Cubes ×{num}"),
+        SplitType::Events { event, num } => format!("{} ×{num}", event_name(event)),
+        SplitType::Custom { index } => format!("Custom split {}", index + 1),
+        SplitType::AllOf { .. } | SplitType::AnyOf { .. } | SplitType::Sequence { .. } => {
+            let separator = match split {
+                SplitType::AllOf { .. } => " & ",
+                SplitType::AnyOf { .. } => " or ",
+                _ => " → ",
+            };
+            let mut name = String::new();
+            for (i, member) in route.members(split).iter().enumerate() {
+                if i != 0 {
+                    name.push_str(separator);
+                }
+                name.push_str(&segment_name(route, *member));
+            }
+            name
+        }
+        level => level_name(level).into(),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an empty LiveSplit run with a segment for each split in the route
+fn write_lss(route: &Route, category_name: &str) -> String {
+    let mut lss = String::new();
+    lss.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    lss.push_str("<Run version=\"1.7.0\">\n");
+    lss.push_str("  <GameIcon />\n");
+    lss.push_str("  <GameName>Runling Run 4</GameName>\n");
+    let _ = writeln!(
+        lss,
+        "  <CategoryName>{}</CategoryName>",
+        escape_xml(category_name)
+    );
+    lss.push_str("  <Metadata>\n");
+    lss.push_str("    <Run id=\"\" />\n");
+    lss.push_str("    <Platform usesEmulator=\"False\"></Platform>\n");
+    lss.push_str("    <Region></Region>\n");
+    lss.push_str("    <Variables />\n");
+    lss.push_str("  </Metadata>\n");
+    lss.push_str("  <Offset>00:00:00</Offset>\n");
+    lss.push_str("  <AttemptCount>0</AttemptCount>\n");
+    lss.push_str("  <AttemptHistory />\n");
+    lss.push_str("  <Segments>\n");
+    for split in route.splits() {
+        lss.push_str("    <Segment>\n");
+        let _ = writeln!(
+            lss,
+            "      <Name>{}</Name>",
+            escape_xml(&segment_name(route, *split))
+        );
+        lss.push_str("      <Icon />\n");
+        lss.push_str("      <SplitTimes>\n");
+        lss.push_str("        <SplitTime name=\"Personal Best\" />\n");
+        lss.push_str("      </SplitTimes>\n");
+        lss.push_str("      <BestSegmentTime />\n");
+        lss.push_str("      <SegmentHistory />\n");
+        lss.push_str("    </Segment>\n");
+    }
+    lss.push_str("  </Segments>\n");
+    lss.push_str("  <AutoSplitterSettings />\n");
+    lss.push_str("</Run>\n");
+    lss
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(route_arg) = args.next() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let custom_splits = args.next();
+    let (route, category_name) = rlr_tools::load_route(&route_arg, custom_splits.as_deref());
+    if route.splits().is_empty() {
+        eprintln!("The route has no splits, see the log above for why");
+        return ExitCode::FAILURE;
+    }
+    print!("{}", write_lss(&route, category_name));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,145 @@
+//! Runs the splitter's scan and split logic against a live process, printing state changes and splits to stdout
+//! instead of driving LiveSplit. This is for debugging the detection outside of LiveSplit, such as for SC2 running
+//! under Wine or Proton.
+//!
+//! Usage: `run [--track-deaths] <PID | PROCESS_NAME> [any | cow | ROUTE] [CUSTOM_SPLITS]`
+//!
+//! The route defaults to `any`, see `load_route` for the others. `--track-deaths` follows deaths like the
+//! experimental setting does. Reading the memory of another process needs
+//! permission to trace it, see `asr-host`.
+
+use std::{env, process::ExitCode, time::Instant};
+
+use asr::{future::next_tick, Process, ProcessId};
+use rlr_tools::{
+    condition::Field,
+    data::{GameData, StateChange},
+    lifecycle::{GameState, Lifecycle},
+    route::Route,
+    split_state::SplitState,
+};
+
+const USAGE: &str =
+    "Usage: run [--track-deaths] <PID | PROCESS_NAME> [any | cow | ROUTE] [CUSTOM_SPLITS]";
+
+/// Prints a line to stdout with the time since the runner started
+fn print_timed(start: Instant, message: &str) {
+    println!("[{:>9.3}s] {message}", start.elapsed().as_secs_f64());
+}
+
+fn describe(state: &StateChange, split_state: &SplitState) -> String {
+    let field = |field| state.field_value(field, split_state);
+    format!(
+        "exp: {:?} ({:+}) level: {:?} pad: {:?} loop: {:?} difficulty: {:?}",
+        field(Field::Exp),
+        field(Field::ExpGained).unwrap_or_default(),
+        field(Field::Level),
+        field(Field::Pad),
+        field(Field::Loop),
+        field(Field::Difficulty),
+    )
+}
+
+/// Follows the game through the route, starting over whenever the tracking is invalidated like the splitter does
+async fn track(process: &Process, route: &Route, track_deaths: bool, start: Instant) {
+    let mut lifecycle = Lifecycle::default();
+    loop {
+        if !lifecycle.state().in_game() {
+            lifecycle.update(GameState::Menu);
+        }
+        print_timed(start, "Scanning for the exp");
+        asr::set_tick_rate(30.0);
+        let mut data = GameData::new(process).await;
+        if track_deaths {
+            data.track_deaths();
+        }
+        asr::set_tick_rate(120.0);
+        print_timed(start, "Found the exp, starting the route");
+        let mut split_index = 0;
+        let mut split = route.splits().get(split_index);
+        let mut split_state = SplitState::from_split(split, route);
+        loop {
+            let state = data.update();
+            if let Some(event) = data
+                .game_state(split.is_none())
+                .and_then(|game_state| lifecycle.update(game_state))
+            {
+                print_timed(
+                    start,
+                    &format!("{event:?}, the game is now {:?}", lifecycle.state()),
+                );
+            }
+            if data.invalid() {
+                print_timed(start, "Tracking was invalidated");
+                break;
+            }
+            if state.field_value(Field::Exp, &split_state[0])
+                != state.field_value(Field::OldExp, &split_state[0])
+            {
+                print_timed(start, &describe(&state, &split_state[0]));
+            }
+            if state.bank_loaded() {
+                print_timed(
+                    start,
+                    &format!(
+                        "Bank loaded, the run counts from exp: {:?}",
+                        data.base_exp()
+                    ),
+                );
+            }
+            if let Some(event) = state.death_event() {
+                let (deaths, level_deaths) = data.deaths();
+                print_timed(
+                    start,
+                    &format!("{event:?}, deaths: {deaths}, on this level: {level_deaths}"),
+                );
+            }
+            if let Some(spl) = split {
+                if state.should_split(&mut split_state, *spl, route) {
+                    print_timed(
+                        start,
+                        &format!("Split {}/{}: {spl}", split_index + 1, route.splits().len()),
+                    );
+                    split_index += 1;
+                    split = route.splits().get(split_index);
+                    split_state = SplitState::from_split(split, route);
+                    if split.is_none() {
+                        print_timed(start, "The route is finished");
+                    }
+                }
+            }
+            next_tick().await;
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1).peekable();
+    let track_deaths = args.next_if_eq("--track-deaths").is_some();
+    let Some(target) = args.next() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let route_arg = args.next().unwrap_or_else(|| "any".into());
+    let custom_splits = args.next();
+    let (route, _) = rlr_tools::load_route(&route_arg, custom_splits.as_deref());
+    if route.splits().is_empty() {
+        eprintln!("The route has no splits, see the log above for why");
+        return ExitCode::FAILURE;
+    }
+    let process = match target.parse::<ProcessId>() {
+        Ok(pid) => Process::attach_by_pid(pid),
+        Err(_) => Process::attach(&target),
+    };
+    let Some(process) = process else {
+        eprintln!(
+            "Could not attach to {target}, check that it is running and that it can be traced"
+        );
+        return ExitCode::FAILURE;
+    };
+    let start = Instant::now();
+    print_timed(start, &format!("Attached to process {}", process.pid()));
+    let _ = asr::run(process.until_closes(track(&process, &route, track_deaths, start)));
+    print_timed(start, "The process closed");
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,62 @@
+//! Host side tools for the auto splitter.
+//! The splitter's own modules are included as they are, with `asr-host` standing in for the parts of `asr` they use,
+//! so the tools always agree with what the splitter does.
+
+use asr::settings;
+use route::{Category, Route};
+
+#[path = "../../src/attach.rs"]
+pub mod attach;
+#[path = "../../src/condition.rs"]
+pub mod condition;
+#[path = "../../src/data.rs"]
+pub mod data;
+#[path = "../../src/deaths.rs"]
+pub mod deaths;
+#[path = "../../src/game_clock.rs"]
+pub mod game_clock;
+#[path = "../../src/il.rs"]
+pub mod il;
+#[path = "../../src/lifecycle.rs"]
+pub mod lifecycle;
+#[path = "../../src/progress.rs"]
+pub mod progress;
+#[path = "../../src/route.rs"]
+pub mod route;
+#[path = "../../src/sigscan.rs"]
+pub mod sigscan;
+#[path = "../../src/split_state.rs"]
+pub mod split_state;
+#[path = "../../src/split_type.rs"]
+pub mod split_type;
+#[path = "../../src/timer_sync.rs"]
+pub mod timer_sync;
+
+/// Same as the splitter's `log!`, but printed straight to stderr
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        ::std::eprintln!($($arg)*)
+    };
+}
+
+/// Loads a route given on the command line, the same way the splitter does through the settings map.
+/// `any` and `cow` are the preset categories, anything else is a route for the Custom category in the same form as
+/// the `route` setting, with any custom conditions given as in the `custom_splits` setting.
+/// Returns the route along with the name of its category.
+pub fn load_route(route: &str, custom_splits: Option<&str>) -> (Route, &'static str) {
+    let (category, category_name) = match route {
+        "any" => (Category::AnyPercent, "Any%"),
+        "cow" => (Category::CowLevelPercent, "Cow Level%"),
+        _ => (Category::Custom, "Custom"),
+    };
+    if category == Category::Custom {
+        let map = settings::Map::new();
+        map.insert("route", &route.into());
+        if let Some(custom_splits) = custom_splits {
+            map.insert("custom_splits", &custom_splits.into());
+        }
+        map.store();
+    }
+    (Route::load(category), category_name)
+}
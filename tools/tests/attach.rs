@@ -0,0 +1,79 @@
+//! Checks how `wait_attach` chooses between several SC2 processes, against instances of `fake_sc2`: that it locks
+//! onto the one whose exp changes, and otherwise onto the newest or oldest one as preferred, leaving out any that
+//! close while it waits.
+//!
+//! Any other `fake_sc2` that is running is considered as well, so everything is checked in a single test.
+
+mod common;
+
+use asr::ProcessId;
+use common::FakeSc2;
+use rlr_tools::attach::{wait_attach_by_names, ProcessPreference};
+
+/// Script for a `fake_sc2` whose exp never changes
+const STATIC_SCRIPT: &str = "set 500, wait 30000";
+
+/// Script for a `fake_sc2` whose exp keeps changing for long enough to be chosen however slow the scans are
+fn live_script() -> String {
+    let mut script = String::from("set 1000");
+    for _ in 0..60 {
+        script.push_str(", wait 500, gain 18");
+    }
+    script
+}
+
+/// Script for a `fake_sc2` whose exp never changes, and which closes after the scans but before the wait is over
+const CLOSING_SCRIPT: &str = "set 500, wait 1500";
+
+/// Starts a `fake_sc2` with the script running
+fn spawn_running(script: &str) -> FakeSc2 {
+    let mut fake_sc2 = FakeSc2::spawn(&[script]);
+    fake_sc2.start();
+    fake_sc2
+}
+
+/// Runs `wait_attach_by_names` against the processes, returning the id of the chosen one
+fn choose(preference: ProcessPreference) -> ProcessId {
+    asr::set_tick_rate(120.0);
+    let (process, _) = asr::run(wait_attach_by_names(&["fake_sc2"], preference));
+    process.pid()
+}
+
+#[test]
+fn chooses_between_processes() {
+    // The live process is the older one, so preferring the newest would not choose it
+    let live = spawn_running(&live_script());
+    let idle = spawn_running(STATIC_SCRIPT);
+    let (live_pid, idle_pid) = (ProcessId::from(live.pid()), ProcessId::from(idle.pid()));
+    assert_eq!(
+        choose(ProcessPreference::Newest),
+        live_pid,
+        "expected the one with a changing exp over a newer {idle_pid}"
+    );
+    drop((live, idle));
+
+    let older = spawn_running(STATIC_SCRIPT);
+    let newer = spawn_running(STATIC_SCRIPT);
+    let (older_pid, newer_pid) = (ProcessId::from(older.pid()), ProcessId::from(newer.pid()));
+    for (preference, expected) in [
+        (ProcessPreference::Newest, newer_pid),
+        (ProcessPreference::Oldest, older_pid),
+    ] {
+        assert_eq!(
+            choose(preference),
+            expected,
+            "out of {older_pid} and {newer_pid} with no exp changing, preferring {preference:?}"
+        );
+    }
+    drop((older, newer));
+
+    // Reading the exp of the newer one fails once it closes, which is not a change of its exp
+    let older = spawn_running(STATIC_SCRIPT);
+    let closing = spawn_running(CLOSING_SCRIPT);
+    let (older_pid, closing_pid) = (ProcessId::from(older.pid()), ProcessId::from(closing.pid()));
+    assert_eq!(
+        choose(ProcessPreference::Newest),
+        older_pid,
+        "expected the one that stays open over a newer {closing_pid} that closed"
+    );
+}
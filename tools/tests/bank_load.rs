@@ -0,0 +1,154 @@
+//! Checks bank loads against `fake_sc2`: that the exp jumping from 0, or from an older exp that nothing was gained
+//! after, to what the bank was saved with is a bank load, which the run counts from, rather than a gain or a reason
+//! to invalidate, while a jump after exp was gained still invalidates.
+
+mod common;
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{condition::Field, data::GameData, split_state::SplitState};
+
+/// What was seen while following the exp
+#[derive(Default, Debug, PartialEq)]
+struct Followed {
+    /// Exp of each bank load
+    bank_loads: Vec<i32>,
+    /// Exp of each gain
+    gains: Vec<i32>,
+    base_exp: Option<i32>,
+    invalidated: bool,
+}
+
+/// Follows the exp until tracking is invalidated, recording the bank loads and gains
+async fn follow_exp(data: &mut GameData<'_>, followed: &mut Followed) {
+    let split_state = SplitState::default();
+    loop {
+        let state = data.update();
+        if data.invalid() {
+            followed.invalidated = true;
+            return;
+        }
+        if state.bank_loaded() {
+            followed.bank_loads.extend(data.exp());
+        }
+        if let Some(gained) = state
+            .field_value(Field::ExpGained, &split_state)
+            .filter(|gained| *gained > 0)
+        {
+            followed.gains.push(gained);
+        }
+        followed.base_exp = data.base_exp();
+        next_tick().await;
+    }
+}
+
+/// Runs the script against `fake_sc2`, with the exp that was last seen before it if given
+fn replay(script: &str, last_seen_exp: Option<i32>) -> Followed {
+    let mut fake_sc2 = FakeSc2::spawn(&[script]);
+    let process = fake_sc2.attach();
+    asr::set_tick_rate(120.0);
+    asr::run(async {
+        let mut data = GameData::new(&process).await;
+        if let Some(exp) = last_seen_exp {
+            data.remember_exp(exp);
+        }
+        fake_sc2.start();
+        // Following is dropped when the process closes, so what was seen is kept out here
+        let mut followed = Followed::default();
+        let _ = process
+            .until_closes(follow_exp(&mut data, &mut followed))
+            .await;
+        followed
+    })
+}
+
+#[test]
+fn bank_with_more_exp_than_can_be_gained_at_once() {
+    let followed = replay(
+        "set 0, wait 500, set 5000, wait 300, gain 2, wait 300",
+        None,
+    );
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![5000],
+            gains: vec![2],
+            base_exp: Some(5000),
+            invalidated: false,
+        }
+    );
+}
+
+#[test]
+fn bank_with_the_exp_it_was_saved_with() {
+    let followed = replay("set 0, wait 500, set 300, wait 300", Some(300));
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![300],
+            gains: vec![],
+            base_exp: Some(300),
+            invalidated: false,
+        }
+    );
+}
+
+#[test]
+fn gain_from_0_that_no_bank_was_saved_with() {
+    let followed = replay("set 0, wait 500, set 300, wait 300", None);
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![],
+            gains: vec![300],
+            base_exp: None,
+            invalidated: false,
+        }
+    );
+}
+
+#[test]
+fn bank_over_an_older_exp() {
+    // The exp of the last game is still there when the game starts, then the bank loads over it
+    let followed = replay(
+        "set 5000, wait 500, set 7000, wait 300, gain 2, wait 300",
+        None,
+    );
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![5000, 7000],
+            gains: vec![2],
+            base_exp: Some(7000),
+            invalidated: false,
+        }
+    );
+    // Or with the bank having less exp than is there, such as after playing on another account
+    let followed = replay("set 5000, wait 500, set 300, wait 300", None);
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![5000, 300],
+            gains: vec![],
+            base_exp: Some(300),
+            invalidated: false,
+        }
+    );
+}
+
+#[test]
+fn jump_after_exp_was_gained() {
+    let followed = replay(
+        "set 1000, wait 500, gain 2, wait 300, set 9000, wait 300",
+        None,
+    );
+    assert_eq!(
+        followed,
+        Followed {
+            bank_loads: vec![1000],
+            gains: vec![2],
+            base_exp: Some(1000),
+            invalidated: true,
+        }
+    );
+}
@@ -0,0 +1,74 @@
+//! Runs `fake_sc2` for the tests that check the splitter's modules against it.
+// Each test only uses some of these
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStderr, Command, Stdio},
+};
+
+use asr::{Address, Process};
+
+/// A running `fake_sc2`, which is killed when dropped
+pub struct FakeSc2 {
+    child: Child,
+    /// Where the exp is, as printed once the ranges are mapped
+    pub exp_address: Address,
+}
+
+impl FakeSc2 {
+    /// Starts `fake_sc2` with the arguments, returning once it has mapped its ranges
+    pub fn spawn(args: &[&str]) -> Self {
+        Self::spawn_from(Path::new(env!("CARGO_BIN_EXE_fake_sc2")), args)
+    }
+    /// Like `spawn`, running the `fake_sc2` binary at the path, such as a copy of it under another name
+    pub fn spawn_from(path: &Path, args: &[&str]) -> Self {
+        let mut child = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("could not run {}: {e}", path.display()));
+        let mut line = String::new();
+        if let Some(stdout) = child.stdout.take() {
+            let _ = BufReader::new(stdout).read_line(&mut line);
+        }
+        let exp_address = line
+            .trim()
+            .strip_prefix("exp address: 0x")
+            .and_then(|address| u64::from_str_radix(address, 16).ok())
+            .map(Address::new);
+        let Some(exp_address) = exp_address else {
+            let _ = child.kill();
+            panic!("fake_sc2 did not print the exp address, but: {line:?}");
+        };
+        Self { child, exp_address }
+    }
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+    /// Attaches to the process, which needs permission to trace it
+    pub fn attach(&self) -> Process {
+        Process::attach_by_pid(self.pid().into())
+            .expect("could not attach to fake_sc2, check that it can be traced")
+    }
+    /// Starts the script, and with it the game loop
+    pub fn start(&mut self) {
+        if let Some(stdin) = self.child.stdin.as_mut() {
+            let _ = writeln!(stdin);
+        }
+    }
+    /// Takes what `fake_sc2` prints about the changes it makes, which it does on stderr
+    pub fn take_stderr(&mut self) -> ChildStderr {
+        self.child.stderr.take().expect("stderr was already taken")
+    }
+}
+
+impl Drop for FakeSc2 {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,138 @@
+//! Checks a whole run on Normal against `fake_sc2`: that `GameData` counts the pads of every level, also when a read
+//! takes in two of them, goes from Diablo into the Cow Level and back into Level 1 on the second loop, and that Any%
+//! ends on Diablo while Cow Level% ends on the Cow Level.
+
+mod common;
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{
+    data::{GameData, StateChange},
+    load_route,
+    route::Route,
+    split_state::{SplitState, SplitStates},
+    split_type::{Difficulty, SplitType},
+};
+
+/// Every level in the order a run goes through them
+const LEVELS: [SplitType; 13] = [
+    SplitType::Level1,
+    SplitType::Level2,
+    SplitType::Level3,
+    SplitType::Bot2000,
+    SplitType::Level4,
+    SplitType::Level5,
+    SplitType::Level6,
+    SplitType::Odin,
+    SplitType::Level7,
+    SplitType::Level8,
+    SplitType::Level9,
+    SplitType::Diablo,
+    SplitType::CowLevel,
+];
+
+/// Script for `fake_sc2`: every pad of every level and every boss on Normal, then the first pad of Level 1 on the
+/// second loop. After the first pad of a level, its pads are gained two at a time, which the game makes on the same
+/// step. Levels are further apart, since a read taking in the end of one level and the start of the next is not
+/// counted.
+fn script() -> String {
+    let mut script = String::from("set 1000, wait 300");
+    for level in LEVELS {
+        let exp = level.per_pad_exp(Difficulty::Normal).unwrap();
+        script.push_str(&format!(", gain {exp}"));
+        for _ in 0..level.pad_count().unwrap_or(1) / 2 {
+            script.push_str(&format!(", wait 60, gain {exp}, gain {exp}"));
+        }
+        script.push_str(", wait 300");
+    }
+    let exp = SplitType::Level1.per_pad_exp(Difficulty::Normal).unwrap();
+    script.push_str(&format!(", gain {exp}, wait 300"));
+    script
+}
+
+/// A route being run through, like the splitter does
+struct Run {
+    route: Route,
+    index: usize,
+    states: SplitStates,
+    /// Levels that were being played when each split fired
+    split_on: Vec<SplitType>,
+}
+
+impl Run {
+    fn new(name: &str) -> Self {
+        let (route, _) = load_route(name, None);
+        let states = SplitState::from_split(route.splits().first(), &route);
+        Self {
+            route,
+            index: 0,
+            states,
+            split_on: Vec::new(),
+        }
+    }
+    fn update(&mut self, state: &StateChange, level: SplitType) {
+        let Some(&split) = self.route.splits().get(self.index) else {
+            return;
+        };
+        if state.should_split(&mut self.states, split, &self.route) {
+            self.split_on.push(level);
+            self.index += 1;
+            self.states = SplitState::from_split(self.route.splits().get(self.index), &self.route);
+        }
+    }
+}
+
+/// Follows the run until tracking is invalidated, recording each level it goes through, along with the loop it was
+/// played on, and running both routes
+async fn follow_run(data: &mut GameData<'_>, runs: &mut [Run], levels: &mut Vec<(SplitType, i32)>) {
+    loop {
+        let state = data.update();
+        if data.invalid() {
+            return;
+        }
+        let loop_num = data.progress(0, 0).map_or(1, |progress| progress.loop_num);
+        let level = data.level();
+        if levels.last() != Some(&(level, loop_num)) {
+            levels.push((level, loop_num));
+        }
+        for run in runs.iter_mut() {
+            run.update(&state, level);
+        }
+        next_tick().await;
+    }
+}
+
+#[test]
+fn runs_through_the_cow_level_into_the_next_loop() {
+    let mut fake_sc2 = FakeSc2::spawn(&[&script()]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let (runs, levels) = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        fake_sc2.start();
+        // Following is dropped when the process closes, so what was seen is kept out here
+        let mut runs = [Run::new("any"), Run::new("cow")];
+        let mut levels = Vec::new();
+        let _ = process
+            .until_closes(follow_run(&mut data, &mut runs, &mut levels))
+            .await;
+        (runs, levels)
+    });
+
+    let mut expected: Vec<_> = LEVELS.iter().map(|level| (*level, 1)).collect();
+    expected.push((SplitType::Level1, 2));
+    assert_eq!(levels, expected);
+
+    let [any, cow] = runs;
+    // Each split is noticed once the level after it has started
+    assert_eq!(any.split_on, LEVELS[1..]);
+    assert_eq!(any.index, any.route.splits().len(), "Any% did not end");
+    assert_eq!(cow.split_on[..12], LEVELS[1..]);
+    assert_eq!(cow.split_on[12..], [SplitType::Level1]);
+    assert_eq!(
+        cow.index,
+        cow.route.splits().len(),
+        "Cow Level% did not end"
+    );
+}
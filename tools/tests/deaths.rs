@@ -0,0 +1,81 @@
+//! Checks deaths and revives against `fake_sc2`: that `GameData` notices the runling dying and being revived, counts
+//! the deaths over the run and on the level, starting the level count over on the next level. The alive flag `fake_sc2`
+//! writes is where the splitter looks for it, which has not been checked against SC2, so deaths are only counted and
+//! never split or reset on.
+
+mod common;
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{data::GameData, deaths::DeathEvent, split_type::SplitType};
+
+/// Exp for a pad of level 1 on Normal, the first of which tells the difficulty
+const PAD_EXP: i32 = 2;
+/// Pads in level 1
+const PADS: usize = 19;
+
+/// Script for `fake_sc2`: dies and is revived on level 1 after its first pad, completes the level, then dies and is
+/// revived once more on level 2
+fn script() -> String {
+    let mut script = format!("set 1000, wait 500, gain {PAD_EXP}, wait 200, die, wait 200, revive");
+    for _ in 1..PADS {
+        script.push_str(&format!(", wait 60, gain {PAD_EXP}"));
+    }
+    script.push_str(", wait 200, die, wait 200, revive, wait 300");
+    script
+}
+
+/// What was seen while following the game
+#[derive(Default)]
+struct Followed {
+    events: Vec<DeathEvent>,
+    /// Deaths over the run and on the level, and the level, as of the last update
+    deaths: (i32, i32),
+    level: Option<SplitType>,
+}
+
+/// Follows the game until tracking is invalidated, recording the deaths and revives
+async fn follow_deaths(data: &mut GameData<'_>, followed: &mut Followed) {
+    loop {
+        let state = data.update();
+        if data.invalid() {
+            return;
+        }
+        if let Some(event) = state.death_event() {
+            followed.events.push(event);
+        }
+        followed.deaths = data.deaths();
+        followed.level = Some(data.level());
+        next_tick().await;
+    }
+}
+
+#[test]
+fn counts_deaths_and_revives() {
+    let mut fake_sc2 = FakeSc2::spawn(&[&script()]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let followed = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.track_deaths();
+        fake_sc2.start();
+        // Following is dropped when the process closes, so what was seen is kept out here
+        let mut followed = Followed::default();
+        let _ = process
+            .until_closes(follow_deaths(&mut data, &mut followed))
+            .await;
+        followed
+    });
+
+    assert_eq!(
+        followed.events,
+        [DeathEvent::Died, DeathEvent::Revived].repeat(2)
+    );
+    assert_eq!(followed.level, Some(SplitType::Level2));
+    assert_eq!(
+        followed.deaths,
+        (2, 1),
+        "expected 2 deaths over the run and 1 on the level"
+    );
+}
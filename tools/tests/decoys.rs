@@ -0,0 +1,75 @@
+//! Checks that `GameData` does not take other counters near the exp for the game loop, against `fake_sc2` running
+//! decoy counters next to it: counters at rates no game speed runs at are passed over, while one at the rate of
+//! another game speed leaves the game loop unknown rather than picking either.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{data::GameData, game_clock::GameSpeed};
+
+/// Script for `fake_sc2`, running for long enough for the game loop to be found
+const SCRIPT: &str = "set 1000, wait 6000";
+/// How long to look for the game loop for, which is longer than calibration takes
+const LOOK_TIME: Duration = Duration::from_secs(5);
+/// How far the game loop can be from the loops the game has been running for, which is a few steps for scheduling
+/// slack
+const LOOP_TOLERANCE: f64 = 10.0;
+
+/// Follows the game until the game loop is found or the look time is over, returning the game loop along with the
+/// time since the start
+async fn look_for_game_loop(data: &mut GameData<'_>, start: Instant) -> Option<(u32, Duration)> {
+    while start.elapsed() < LOOK_TIME {
+        data.update();
+        if data.invalid() {
+            return None;
+        }
+        if let Some(game_loop) = data.game_loop() {
+            return Some((game_loop, start.elapsed()));
+        }
+        next_tick().await;
+    }
+    None
+}
+
+/// Runs `fake_sc2` at the Faster game speed with decoys at the rates, returning the game loop if it was found
+fn find_game_loop(decoys: &str) -> Option<(u32, Duration)> {
+    let mut fake_sc2 = FakeSc2::spawn(&["--decoys", decoys, SCRIPT]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.follow_game_loop();
+        // The game loop starts with the script
+        fake_sc2.start();
+        let start = Instant::now();
+        process
+            .until_closes(look_for_game_loop(&mut data, start))
+            .await
+            .ok()
+            .flatten()
+    })
+}
+
+#[test]
+fn passes_over_counters_at_other_rates() {
+    // Every frame at 60 and 144 FPS, and a rate between the Slower and Slow game speeds
+    let Some((game_loop, elapsed)) = find_game_loop("60,144,11.2") else {
+        panic!("the game loop was not found next to the decoys");
+    };
+    let expected = GameSpeed::Faster.loops_per_second() * elapsed.as_secs_f64();
+    assert!(
+        (game_loop as f64 - expected).abs() <= LOOP_TOLERANCE,
+        "found a counter at {game_loop} rather than the game loop at about {expected:.0}"
+    );
+}
+
+#[test]
+fn leaves_the_game_loop_unknown_next_to_another_game_speed() {
+    // The rate of the Normal game speed
+    let found = find_game_loop("16");
+    assert_eq!(found.map(|(game_loop, _)| game_loop), None);
+}
@@ -0,0 +1,146 @@
+//! Checks individual level mode against `fake_sc2`: that `IlRun` starts the chosen level when it is reached from the
+//! level before it, or when the game starts on it, splits on its pads and its completion, and resets when the game is
+//! left, to start over when the level is played again in the next game.
+
+mod common;
+
+use asr::{future::next_tick, Process};
+use common::FakeSc2;
+use rlr_tools::{
+    data::GameData,
+    il::{IlAction, IlRun},
+    load_route,
+    route::Route,
+    split_type::{Difficulty, SplitType, PAD_COUNT},
+};
+
+/// Script steps that cross the pads of the level on Normal, one at a time
+fn pads(level: SplitType, count: i32) -> String {
+    let exp = level.per_pad_exp(Difficulty::Normal).unwrap();
+    (0..count)
+        .map(|_| format!(", gain {exp}, wait 60"))
+        .collect()
+}
+
+/// Follows games until the process closes, like the splitter does in IL mode, adding what the timer is told to do to
+/// the actions along with the level that tracking is on then. Each game is tracked from the level given, if any.
+async fn follow_il(
+    process: &Process,
+    il_run: &mut IlRun,
+    start_at: Option<SplitType>,
+    route: &Route,
+    actions: &mut Vec<(IlAction, SplitType)>,
+) {
+    loop {
+        let mut data = GameData::new(process).await;
+        if let Some(level) = start_at {
+            data.start_at(level);
+        }
+        loop {
+            let state = data.update();
+            if data.invalid() {
+                actions.extend(il_run.tracking_lost().map(|action| (action, data.level())));
+                break;
+            }
+            if let Some(action) = il_run.update(&state, route) {
+                actions.push((action, data.level()));
+            }
+            next_tick().await;
+        }
+    }
+}
+
+/// Runs the script against `fake_sc2` in IL mode for the level, returning what the timer was told to do
+fn replay(
+    script: &str,
+    level: SplitType,
+    split_pads: bool,
+    start_at: Option<SplitType>,
+) -> Vec<(IlAction, SplitType)> {
+    let mut fake_sc2 = FakeSc2::spawn(&[script]);
+    let process = fake_sc2.attach();
+    let (route, _) = load_route("any", None);
+
+    asr::set_tick_rate(120.0);
+    asr::run(async {
+        let mut il_run = IlRun::new(level, split_pads);
+        let mut actions = Vec::new();
+        fake_sc2.start();
+        // Following is dropped when the process closes, so what was seen is kept out here
+        let _ = process
+            .until_closes(follow_il(
+                &process,
+                &mut il_run,
+                start_at,
+                &route,
+                &mut actions,
+            ))
+            .await;
+        actions
+    })
+}
+
+#[test]
+fn starts_a_later_level_when_the_level_before_it_is_completed() {
+    let script = format!(
+        "set 1000, wait 300{}{}, wait 300",
+        pads(SplitType::Level1, PAD_COUNT),
+        pads(SplitType::Level2, PAD_COUNT)
+    );
+    let actions = replay(&script, SplitType::Level2, false, None);
+    assert_eq!(
+        actions,
+        [
+            (IlAction::Start, SplitType::Level2),
+            (IlAction::Split, SplitType::Level3)
+        ]
+    );
+}
+
+#[test]
+fn starts_a_level_the_game_starts_on_at_its_first_pad() {
+    let script = format!(
+        "set 1000, wait 300{}, wait 300",
+        pads(SplitType::Level7, PAD_COUNT)
+    );
+    let actions = replay(&script, SplitType::Level7, true, Some(SplitType::Level7));
+    let mut expected = vec![(IlAction::Start, SplitType::Level7)];
+    expected.extend((1..PAD_COUNT - 1).map(|_| (IlAction::Split, SplitType::Level7)));
+    expected.push((IlAction::Split, SplitType::Level8));
+    assert_eq!(actions, expected);
+}
+
+#[test]
+fn starts_a_boss_the_game_starts_on_with_the_game() {
+    let exp = SplitType::Odin.boss_exp(Difficulty::Hard).unwrap();
+    let script = format!("set 1000, wait 300, gain {exp}, wait 300");
+    let actions = replay(&script, SplitType::Odin, false, Some(SplitType::Odin));
+    assert_eq!(
+        actions,
+        [
+            (IlAction::Start, SplitType::Odin),
+            (IlAction::Split, SplitType::Level7)
+        ]
+    );
+}
+
+#[test]
+fn resets_when_the_game_is_left_and_starts_over_in_the_next() {
+    // The next game is joined after the scan for it has started, and played once it has found the exp
+    let script = format!(
+        "set 1000, wait 300{}, leave, wait 500, join, wait 3000{}, wait 300",
+        pads(SplitType::Level1, 2),
+        pads(SplitType::Level1, 2)
+    );
+    let actions = replay(&script, SplitType::Level1, true, None);
+    assert_eq!(
+        actions,
+        [
+            (IlAction::Start, SplitType::Level1),
+            (IlAction::Split, SplitType::Level1),
+            (IlAction::Reset, SplitType::Level1),
+            (IlAction::Start, SplitType::Level1),
+            (IlAction::Split, SplitType::Level1)
+        ]
+    );
+}
@@ -0,0 +1,107 @@
+//! Checks the splits files that `lss` writes: that they are well-formed XML with a segment for each split of the
+//! route, named and counted right for each category and for a custom route.
+
+use std::process::Command;
+
+use rlr_tools::{load_route, route::Category};
+
+/// Runs `lss` with the arguments, returning what it wrote, or None if it failed
+fn lss(args: &[&str]) -> Option<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_lss"))
+        .args(args)
+        .output()
+        .expect("could not run lss");
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8(output.stdout).expect("lss wrote something other than UTF-8"))
+}
+
+/// Finds the element of the node with the name
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> roxmltree::Node<'a, 'input> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .unwrap_or_else(|| panic!("there is no {name} in {:?}", node.tag_name().name()))
+}
+
+/// Parses a splits file, returning its category name and the names of its segments
+fn parse(lss: &str) -> (String, Vec<String>) {
+    let document = roxmltree::Document::parse(lss).expect("the splits are not well-formed XML");
+    let run = document.root_element();
+    assert_eq!(run.tag_name().name(), "Run");
+    let category = child(run, "CategoryName")
+        .text()
+        .unwrap_or_default()
+        .to_string();
+    let segments = child(run, "Segments")
+        .children()
+        .filter(|node| node.is_element())
+        .map(|segment| {
+            assert_eq!(segment.tag_name().name(), "Segment");
+            child(segment, "Name")
+                .text()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect();
+    (category, segments)
+}
+
+/// Names of the levels as the segments of the preset categories are named
+const LEVELS: [&str; 13] = [
+    "Level 1",
+    "Level 2",
+    "Level 3",
+    "Bot2000",
+    "Level 4",
+    "Level 5",
+    "Level 6",
+    "Odin",
+    "Level 7",
+    "Level 8",
+    "Level 9",
+    "Diablo",
+    "Cow Level",
+];
+
+#[test]
+fn writes_a_segment_for_each_split_of_the_categories() {
+    for (arg, category, name, levels) in [
+        ("any", Category::AnyPercent, "Any%", 12),
+        ("cow", Category::CowLevelPercent, "Cow Level%", 13),
+    ] {
+        let (category_name, segments) = parse(&lss(&[arg]).unwrap());
+        assert_eq!(category_name, name);
+        assert_eq!(segments, LEVELS[..levels]);
+        let (route, _) = load_route(arg, None);
+        assert_eq!(segments.len(), route.splits().len(), "{category:?}");
+    }
+}
+
+#[test]
+fn names_the_segments_of_a_custom_route() {
+    let lss = lss(&[
+        "Level1, AllOf(PadsCrossed(5), EnergyCubes(3)), CompleteForLevel(9), Custom(0), Diablo",
+        "level == Odin && cubes >= 3",
+    ])
+    .unwrap();
+    let (category_name, segments) = parse(&lss);
+    assert_eq!(category_name, "Custom");
+    assert_eq!(
+        segments,
+        [
+            "Level 1",
+            // Written as `&amp;`, which reads back as `&`
+            "Pads ×5 & Cubes ×3",
+            "Level 7 complete",
+            "Custom split 1",
+            "Diablo",
+        ]
+    );
+}
+
+#[test]
+fn fails_for_a_route_without_splits() {
+    assert_eq!(lss(&["NotASplit"]), None);
+    assert_eq!(lss(&[]), None);
+}
@@ -0,0 +1,91 @@
+//! Checks which memory ranges the exp scan would read in the recorded maps in `fixtures/maps`, and that the exp is
+//! among them. The only recording so far is of `fake_sc2 --wine`, which is laid out the way the Wine layout expects,
+//! so it catches changes to the scan rather than showing that it works under Wine.
+//!
+//! A recording is the contents of `/proc/<pid>/maps` for SC2, after a header of `#` lines that give the layout to scan
+//! it with and where the exp was, such as `# layout: Wine` and `# exp: 0x202581FFC`.
+
+use std::{fs, path::Path};
+
+use asr::{Address, MemoryRange};
+use rlr_tools::sigscan::{ScanLayout, WINE_MAX_RANGE_SIZE};
+
+/// Checks a recording, returning an error if the range with the exp would not be scanned
+fn check(path: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut layout = None;
+    let mut exp = None;
+    for header in text.lines().filter_map(|line| line.strip_prefix('#')) {
+        match header.trim().split_once(": ") {
+            Some(("layout", "Windows")) => layout = Some(ScanLayout::Windows),
+            Some(("layout", "Wine")) => layout = Some(ScanLayout::Wine),
+            Some(("exp", address)) => {
+                exp = address
+                    .strip_prefix("0x")
+                    .and_then(|address| u64::from_str_radix(address, 16).ok())
+                    .map(Address::new)
+            }
+            _ => (),
+        }
+    }
+    let layout = layout.ok_or("there is no `# layout:` header")?;
+    let exp = exp.ok_or("there is no `# exp:` header")?;
+    let ranges: Vec<MemoryRange> = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| MemoryRange::parse(line).ok_or(format!("could not parse: {line}")))
+        .collect::<Result<_, _>>()?;
+    let mut scanned_ranges = 0;
+    let mut scanned_bytes = 0;
+    let mut exp_scanned = false;
+    for (index, range) in ranges.iter().enumerate() {
+        let (base, size) = range.range().map_err(|_| "range without an address")?;
+        let flags = range.flags().map_err(|_| "range without flags")?;
+        if layout.should_scan(index, base, size, flags) {
+            scanned_ranges += 1;
+            scanned_bytes += size;
+            exp_scanned |= (base.value()..base.value() + size).contains(&exp.value());
+        }
+    }
+    println!(
+        "{}: {layout:?} layout scans {scanned_ranges} of {} ranges ({} KiB)",
+        path.display(),
+        ranges.len(),
+        scanned_bytes >> 10,
+    );
+    match exp_scanned {
+        true => Ok(()),
+        false => Err(format!("the exp at {exp:?} is not scanned")),
+    }
+}
+
+#[test]
+fn recorded_maps_scan_the_exp() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/maps");
+    let mut paths: Vec<_> = fs::read_dir(&fixtures)
+        .unwrap_or_else(|e| panic!("could not read {}: {e}", fixtures.display()))
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "there are no recordings to check");
+    let failures: Vec<_> = paths
+        .iter()
+        .filter_map(|path| Some(format!("{}: {}", path.display(), check(path).err()?)))
+        .collect();
+    assert!(failures.is_empty(), "{failures:#?}");
+}
+
+#[test]
+fn wine_skips_ranges_too_big_for_the_heap() {
+    let scanned = |line: &str| {
+        let range = MemoryRange::parse(line).unwrap();
+        let (base, size) = range.range().unwrap();
+        ScanLayout::Wine.should_scan(0, base, size, range.flags().unwrap())
+    };
+    let end = 0x200000000 + WINE_MAX_RANGE_SIZE;
+    assert!(scanned(&format!("200000000-{end:x} rw-p 00000000 00:00 0")));
+    let end = end + 0x1000;
+    assert!(!scanned(&format!(
+        "200000000-{end:x} rw-p 00000000 00:00 0"
+    )));
+}
@@ -0,0 +1,83 @@
+//! Replays a game with pauses against `fake_sc2` and checks that the pauses are left out of game time: that
+//! `GameData` finds the game loop and notices every pause and resume, and that game time paused and resumed along
+//! with it comes out as the time the game was running.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use asr::{future::next_tick, timer};
+use common::FakeSc2;
+use rlr_tools::{data::GameData, game_clock::ClockEvent};
+
+/// Script for `fake_sc2`, running for long enough before the first pause for the game loop to be found
+const SCRIPT: &str =
+    "set 1000, wait 3000, pause, wait 2000, unpause, wait 2000, pause, wait 1000, unpause, wait 1500";
+/// Pauses in the script
+const PAUSES: usize = 2;
+/// How long the script is paused for in total
+const PAUSED_TIME: Duration = Duration::from_secs(3);
+/// How far game time can be off for each pause, which is how long the game loop has to stand still for (3 steps of
+/// the game loop at the Faster game speed, 134ms) plus a tick for noticing the pause and the resume each
+const PAUSE_LATENCY: Duration = Duration::from_millis(150);
+
+/// Follows the exp and the game loop until the process closes, pausing and resuming game time along with the game
+/// like the splitter does. Each pause and resume is added to the events along with when it was noticed.
+async fn replay(data: &mut GameData<'_>, start: Instant, events: &mut Vec<(ClockEvent, Duration)>) {
+    loop {
+        let state = data.update();
+        if data.invalid() {
+            return;
+        }
+        if let Some(event) = state.clock_event() {
+            match event {
+                ClockEvent::Paused => timer::pause_game_time(),
+                ClockEvent::Resumed => timer::resume_game_time(),
+            }
+            events.push((event, start.elapsed()));
+        }
+        next_tick().await;
+    }
+}
+
+#[test]
+fn pauses_are_left_out_of_game_time() {
+    let mut fake_sc2 = FakeSc2::spawn(&[SCRIPT]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let (events, real_time, game_time) = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.follow_game_loop();
+        // Start the script and game time together
+        fake_sc2.start();
+        let start = Instant::now();
+        timer::set_game_time(asr::time::Duration::ZERO);
+        timer::resume_game_time();
+        // The replay is dropped when the process closes, so the events are kept out here
+        let mut events = Vec::new();
+        let _ = process
+            .until_closes(replay(&mut data, start, &mut events))
+            .await;
+        // Game time is kept in asr's Duration, which can be negative where real time can't
+        let game_time = Duration::try_from(timer::game_time()).unwrap();
+        (events, start.elapsed(), game_time)
+    });
+
+    for (event, at) in &events {
+        println!("{event:?} at {:.3}s", at.as_secs_f64());
+    }
+    let expected = [ClockEvent::Paused, ClockEvent::Resumed].repeat(PAUSES);
+    assert!(
+        events.iter().map(|(event, _)| *event).eq(expected),
+        "noticed {events:?}, expected {PAUSES} pauses and resumes"
+    );
+    let running_time = real_time.saturating_sub(PAUSED_TIME);
+    assert!(
+        game_time.abs_diff(running_time) <= PAUSE_LATENCY * PAUSES as u32,
+        "game time is {:.3}s of {:.3}s, expected the {:.3}s the game was running",
+        game_time.as_secs_f64(),
+        real_time.as_secs_f64(),
+        running_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,99 @@
+//! Checks the exp scan and tracking end to end against `fake_sc2`: that `find_exp_pattern` finds the exp, and that
+//! `GameData` follows the exp as the script changes it.
+
+mod common;
+
+use std::{env, fs, time::Instant};
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{
+    data::GameData,
+    sigscan::{find_exp_pattern, ScanLayout},
+};
+
+/// Script for `fake_sc2`, waiting long enough between changes for every one of them to be read
+const SCRIPT: &str = "set 1000, wait 500, gain 18, wait 250, gain 18, wait 250, gain 300, wait 250";
+/// Every exp the script goes through
+const EXPECTED_EXP: [i32; 4] = [1000, 1018, 1036, 1336];
+
+/// Reads the exp on every tick until tracking is invalidated, adding each exp that was read to the exps
+async fn follow_exp(data: &mut GameData<'_>, exps: &mut Vec<i32>) {
+    loop {
+        data.update();
+        if data.invalid() {
+            return;
+        }
+        if let Some(exp) = data.exp() {
+            if exps.last() != Some(&exp) {
+                exps.push(exp);
+            }
+        }
+        next_tick().await;
+    }
+}
+
+#[test]
+fn finds_and_follows_the_exp() {
+    let mut fake_sc2 = FakeSc2::spawn(&[SCRIPT]);
+    let process = fake_sc2.attach();
+
+    let scan_start = Instant::now();
+    let address = asr::run(find_exp_pattern(&process));
+    println!("the scan took {:.3}s", scan_start.elapsed().as_secs_f64());
+    assert_eq!(address, Some(fake_sc2.exp_address));
+
+    asr::set_tick_rate(120.0);
+    let exps = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        // Start the script now that the exp is known
+        fake_sc2.start();
+        // Following is dropped when the process closes, so the exps are kept out here
+        let mut exps = Vec::new();
+        let _ = process.until_closes(follow_exp(&mut data, &mut exps)).await;
+        exps
+    });
+    assert_eq!(exps, EXPECTED_EXP);
+}
+
+#[test]
+fn finds_the_exp_under_wine() {
+    // Run fake_sc2 under the name of Wine's preloader, which is what the process runs under Wine
+    let dir = env::temp_dir().join(format!("rlr-tools-wine-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let wine = dir.join("wine64-preloader");
+    fs::copy(env!("CARGO_BIN_EXE_fake_sc2"), &wine).unwrap();
+    // The target is among the first 1000 ranges, which only the Wine layout scans
+    let fake_sc2 = FakeSc2::spawn_from(&wine, &["--wine", "--ranges", "400", "--target", "300"]);
+    let process = fake_sc2.attach();
+    let layout = ScanLayout::detect(&process);
+    let address = asr::run(find_exp_pattern(&process));
+    let expected_address = fake_sc2.exp_address;
+    drop(fake_sc2);
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(layout, ScanLayout::Wine);
+    assert_eq!(address, Some(expected_address));
+}
+
+#[test]
+fn tells_wine_from_the_executable() {
+    for (path, layout) in [
+        // The runtime gives paths of Windows processes in the form WASI sees them
+        (
+            "/mnt/c/Program Files (x86)/StarCraft II/Versions/Base93333/SC2_x64.exe",
+            ScanLayout::Windows,
+        ),
+        (
+            r"C:\Program Files (x86)\StarCraft II\Versions\Base93333\SC2_x64.exe",
+            ScanLayout::Windows,
+        ),
+        (
+            "/home/user/.steam/steam/steamapps/common/Proton 9.0 (Beta)/files/bin/wine64-preloader",
+            ScanLayout::Wine,
+        ),
+        ("/usr/bin/wine64", ScanLayout::Wine),
+        ("/usr/lib/wine/wine-preloader", ScanLayout::Wine),
+    ] {
+        assert_eq!(ScanLayout::from_executable(path), layout, "{path}");
+    }
+}
@@ -0,0 +1,143 @@
+//! Checks the game speed and game loop time against `fake_sc2` at several game speeds: that `GameData` tells the game
+//! speed from the game loop, and that converting the game loop to time at that speed gives the time the game has been
+//! running for. Also checks that a change of game speed is noticed, and that a pause is not taken for one.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{data::GameData, game_clock::GameSpeed};
+
+/// Script for `fake_sc2`, running for long enough for the game speed to be told
+const SCRIPT: &str = "set 1000, wait 10000";
+/// Script for `fake_sc2` that pauses once the game speed has been told, then changes the game speed to Normal
+const CHANGE_SCRIPT: &str =
+    "set 1000, wait 9000, pause, wait 2000, unpause, wait 6000, speed normal, wait 12000";
+/// When the script changes the game speed
+const CHANGE_TIME: Duration = Duration::from_secs(17);
+/// How far the time of the game loop can be from the time the game has been running, which is a step of the game
+/// loop at the Slower speed and some scheduling slack
+const TIME_TOLERANCE: Duration = Duration::from_millis(200);
+
+/// Follows the game until the game speed is told, returning it along with the time of the game loop then and the time
+/// since the start
+async fn wait_for_speed(
+    data: &mut GameData<'_>,
+    start: Instant,
+) -> Option<(GameSpeed, Duration, Duration)> {
+    loop {
+        data.update();
+        if data.invalid() {
+            return None;
+        }
+        if let (Some(game_loop), Some(game_speed)) = (data.game_loop(), data.game_speed()) {
+            let running_time = start.elapsed();
+            return Some((
+                game_speed,
+                Duration::try_from(game_speed.loops_to_duration(game_loop)).unwrap(),
+                running_time,
+            ));
+        }
+        next_tick().await;
+    }
+}
+
+/// Runs `fake_sc2` at the game speed, as it takes it, and checks what is told from it
+fn check_speed(fake_sc2_speed: &str, expected: GameSpeed) {
+    let mut fake_sc2 = FakeSc2::spawn(&["--speed", fake_sc2_speed, SCRIPT]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let told = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.follow_game_loop();
+        // The game loop starts with the script
+        fake_sc2.start();
+        let start = Instant::now();
+        process
+            .until_closes(wait_for_speed(&mut data, start))
+            .await
+            .ok()
+            .flatten()
+    });
+    let Some((game_speed, game_loop_time, running_time)) = told else {
+        panic!("the game speed was not told for {fake_sc2_speed}");
+    };
+    assert_eq!(game_speed, expected);
+    assert!(
+        game_loop_time.abs_diff(running_time) <= TIME_TOLERANCE,
+        "the game loop at {game_speed:?} is {:.3}s, expected the {:.3}s the game was running",
+        game_loop_time.as_secs_f64(),
+        running_time.as_secs_f64()
+    );
+}
+
+#[test]
+fn tells_slower() {
+    check_speed("slower", GameSpeed::Slower);
+}
+
+#[test]
+fn tells_normal() {
+    check_speed("normal", GameSpeed::Normal);
+}
+
+#[test]
+fn tells_faster() {
+    check_speed("faster", GameSpeed::Faster);
+}
+
+/// Follows the game until the process closes, adding each game speed told to the speeds along with when it was told
+async fn follow_speed(
+    data: &mut GameData<'_>,
+    start: Instant,
+    speeds: &mut Vec<(GameSpeed, Duration)>,
+) {
+    loop {
+        data.update();
+        if data.invalid() {
+            return;
+        }
+        let game_speed = data.game_speed();
+        if game_speed.is_some() && speeds.last().map(|(speed, _)| *speed) != game_speed {
+            speeds.extend(game_speed.map(|speed| (speed, start.elapsed())));
+        }
+        next_tick().await;
+    }
+}
+
+#[test]
+fn notices_the_game_speed_changing() {
+    let mut fake_sc2 = FakeSc2::spawn(&[CHANGE_SCRIPT]);
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let speeds = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.follow_game_loop();
+        fake_sc2.start();
+        let start = Instant::now();
+        let mut speeds = Vec::new();
+        let _ = process
+            .until_closes(follow_speed(&mut data, start, &mut speeds))
+            .await;
+        speeds
+    });
+    let before: Vec<_> = speeds
+        .iter()
+        .filter(|(_, told)| *told < CHANGE_TIME)
+        .map(|(speed, _)| *speed)
+        .collect();
+    assert_eq!(
+        before,
+        [GameSpeed::Faster],
+        "the pause was taken for a change of game speed"
+    );
+    assert_eq!(
+        speeds.last().map(|(speed, _)| *speed),
+        Some(GameSpeed::Normal),
+        "the change of game speed was not noticed"
+    );
+}
@@ -0,0 +1,86 @@
+//! Checks that changes to the exp are timed to the game loop they were made on, against `fake_sc2`: that the game
+//! loop `GameData` reads along with the exp is the one `fake_sc2` made each change on, however late the tick that
+//! noticed it came. Splits set game time from that loop when game time is set from the game loop.
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader},
+    thread,
+};
+
+use asr::future::next_tick;
+use common::FakeSc2;
+use rlr_tools::{condition::Field, data::GameData, split_state::SplitState};
+
+/// Script for `fake_sc2`, running for long enough before the first gain for the game loop to be found
+const SCRIPT: &str =
+    "set 1000, wait 3000, gain 18, wait 700, gain 18, wait 700, gain 300, wait 500";
+/// Exp gains in the script
+const GAINS: usize = 3;
+
+/// Parses a line that `fake_sc2` prints when it changes the exp, into the exp and the game loop it was changed on
+fn parse_exp_line(line: &str) -> Option<(i32, u32)> {
+    let (exp, game_loop) = line.strip_prefix("exp: ")?.split_once(" at loop ")?;
+    Some((exp.parse().ok()?, game_loop.parse().ok()?))
+}
+
+/// Follows the exp until tracking is invalidated, adding each exp gained to the gains along with the game loop it
+/// was read with
+async fn follow_gains(data: &mut GameData<'_>, gains: &mut Vec<(i32, Option<u32>)>) {
+    let split_state = SplitState::default();
+    loop {
+        let state = data.update();
+        if data.invalid() {
+            return;
+        }
+        if state
+            .field_value(Field::ExpGained, &split_state)
+            .is_some_and(|gained| gained > 0)
+        {
+            if let Some(exp) = data.exp() {
+                gains.push((exp, state.game_loop()));
+            }
+        }
+        next_tick().await;
+    }
+}
+
+#[test]
+fn gains_are_read_with_their_game_loop() {
+    let mut fake_sc2 = FakeSc2::spawn(&[SCRIPT]);
+    // Collect the changes fake_sc2 makes while the game is followed
+    let stderr = fake_sc2.take_stderr();
+    let changes = thread::spawn(move || {
+        BufReader::new(stderr)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| parse_exp_line(&line))
+            .collect::<Vec<_>>()
+    });
+    let process = fake_sc2.attach();
+
+    asr::set_tick_rate(120.0);
+    let gains = asr::run(async {
+        let mut data = GameData::new(&process).await;
+        data.follow_game_loop();
+        // Start the script, and with it the game loop
+        fake_sc2.start();
+        // Following is dropped when the process closes, so the gains are kept out here
+        let mut gains = Vec::new();
+        let _ = process
+            .until_closes(follow_gains(&mut data, &mut gains))
+            .await;
+        gains
+    });
+    drop(fake_sc2);
+    let changes = changes.join().unwrap_or_default();
+
+    // The script starts at its first exp, so every change is a gain
+    assert_eq!(changes.len(), GAINS, "fake_sc2 made {changes:?}");
+    let made: Vec<_> = changes
+        .iter()
+        .map(|(exp, game_loop)| (*exp, Some(*game_loop)))
+        .collect();
+    assert_eq!(gains, made, "the gains were read at other game loops");
+}
@@ -0,0 +1,91 @@
+//! Checks `TimerSync` against the run that `asr-host` keeps in place of LiveSplit: that splits, skips, undos and
+//! resets done by hand are followed, that LiveSplit catching up with the splitter's own split a tick late is not
+//! taken for the runner undoing it, and that splits that do not line up with the route are warned about when the
+//! timer starts.
+
+use asr::timer;
+use rlr_tools::{
+    load_route,
+    route::Route,
+    timer_sync::{TimerEvent, TimerSync},
+};
+
+/// Timer variable that mismatches are warned about in
+const SEGMENT_WARNING: &str = "Segment warning";
+
+/// Loads the Any% route, with splits in LiveSplit that have a segment for each of its splits
+fn any_percent() -> Route {
+    let (route, _) = load_route("any", None);
+    timer::set_segments(route.splits().len());
+    route
+}
+
+/// Starts the timer with splits that have the number of segments, returning the warning about them, which is empty
+/// when there is none
+fn warning_on_start(route: &Route, segments: usize, split_index: usize) -> String {
+    timer::set_segments(segments);
+    let mut timer_sync = TimerSync::default();
+    timer_sync.update(route, split_index);
+    timer::start();
+    timer_sync.update(route, split_index);
+    timer::variable(SEGMENT_WARNING).unwrap_or_default()
+}
+
+#[test]
+fn follows_what_is_done_by_hand() {
+    let route = any_percent();
+    let mut timer_sync = TimerSync::default();
+    assert_eq!(timer_sync.update(&route, 0), None);
+    timer::start();
+    assert_eq!(timer_sync.update(&route, 0), None);
+    timer::split();
+    assert_eq!(timer_sync.update(&route, 0), Some(TimerEvent::MovedTo(1)));
+    timer::skip_split();
+    assert_eq!(timer_sync.update(&route, 1), Some(TimerEvent::MovedTo(2)));
+    timer::undo_split();
+    timer::undo_split();
+    assert_eq!(timer_sync.update(&route, 2), Some(TimerEvent::MovedTo(0)));
+    timer::reset();
+    assert_eq!(timer_sync.update(&route, 0), Some(TimerEvent::Reset));
+    assert_eq!(timer_sync.update(&route, 0), None);
+}
+
+#[test]
+fn waits_a_tick_for_its_own_split() {
+    let route = any_percent();
+    let mut timer_sync = TimerSync::default();
+    timer::start();
+    assert_eq!(timer_sync.update(&route, 0), None);
+    // LiveSplit takes the split a tick late
+    timer_sync.splitter_moved_to(1);
+    assert_eq!(timer_sync.update(&route, 1), None);
+    timer::split();
+    assert_eq!(timer_sync.update(&route, 1), None);
+    // LiveSplit takes the split right away
+    timer::split();
+    timer_sync.splitter_moved_to(2);
+    assert_eq!(timer_sync.update(&route, 2), None);
+    // LiveSplit does not take the split, so it is followed back from the tick after
+    timer_sync.splitter_moved_to(3);
+    assert_eq!(timer_sync.update(&route, 3), None);
+    assert_eq!(timer_sync.update(&route, 3), Some(TimerEvent::MovedTo(2)));
+}
+
+#[test]
+fn warns_about_splits_that_do_not_match_the_route() {
+    let route = any_percent();
+    let splits = route.splits().len();
+    assert_eq!(warning_on_start(&route, splits, 0), "");
+    let fewer = warning_on_start(&route, splits - 1, 0);
+    assert!(fewer.contains("fewer segments"), "{fewer}");
+    let more = warning_on_start(&route, splits + 1, 0);
+    assert!(more.contains("more segments"), "{more}");
+    // The route is ahead of LiveSplit, such as when a run was resumed after the timer was reset
+    let behind = warning_on_start(&route, splits, 2);
+    assert_eq!(
+        behind,
+        "LiveSplit is on segment 1 but the route is on split 3"
+    );
+    // The warning goes away once the splits match again
+    assert_eq!(warning_on_start(&route, splits, 0), "");
+}